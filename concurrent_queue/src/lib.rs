@@ -143,6 +143,35 @@ impl<T> ConcurrentQueue<T> {
             QueueFlavor::Unbounded(ub) => ub.pop(),
         }
     }
+
+    /// the number of values in the queue
+    pub fn len(&self) -> usize {
+        match &self.inner {
+            QueueFlavor::Unbounded(ub) => ub.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match &self.inner {
+            QueueFlavor::Unbounded(ub) => ub.is_empty(),
+        }
+    }
+
+    /// closes the queue, so every push after this fails.
+    /// values already in the queue can still be popped.
+    ///
+    /// returns `true` if this call closed the queue
+    pub fn close(&self) -> bool {
+        match &self.inner {
+            QueueFlavor::Unbounded(ub) => ub.close(),
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        match &self.inner {
+            QueueFlavor::Unbounded(ub) => ub.is_closed(),
+        }
+    }
 }
 
 enum QueueFlavor<T> {
@@ -24,6 +24,7 @@ pub mod window;
 pub mod errors;
 
 pub use errors::Result;
+use yage_executor::{Executor, NotThreadSafe};
 use yage_util::list::LinkedList;
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -32,7 +33,6 @@ pub struct Dimensions {
     pub height: u32,
 }
 
-pub struct Executor;
 pub struct Assets;
 
 pub struct EngineBuilder<'a, S> {
@@ -42,7 +42,7 @@ pub struct EngineBuilder<'a, S> {
 pub struct Engine<S> {
     window: Window<S>,
     state: Option<S>,
-    executor: Executor,
+    executor: Executor<NotThreadSafe>,
     assets: Assets,
     components: ComponentList<S>,
 }
@@ -84,21 +84,17 @@ impl<S> EngineBuilder<S> {
     
 
     pub fn build(self) -> crate::Result<Engine<S>> {
-        let executor = Executor;
-        let state = self.state;
-        let h = executor.spawn(async move {
-            match self.state {
-                Some(state) => {
-                    state.await?
-                },
-                None => {
-                    Err(crate::Error::new(crate::errors::ErrorKind::StateNotInitialized))
-                }
+        let executor = Executor::new_unsync();
+        // the initializer isn't `'static`, so it's run right here instead of being spawned
+        let state = match self.state {
+            Some(state) => executor.block_on(Box::into_pin(state))?,
+            None => {
+                return Err(crate::Error::new(
+                    crate::errors::ErrorKind::StateNotInitialized,
+                ))
             }
-        });
-        
-        let state = h.join()?;
-        
+        };
+
         Ok(Engine {
             window: todo!(),
             state: Some(state),
@@ -24,4 +24,14 @@ impl<S> Async<S> {
             components: LinkedList::new(),
         }
     }
+
+    /// drives the async components for at most `budget`, returning how many tasks are still ready.
+    /// this is called once per frame, so async updates can't hold up the main loop
+    #[cfg(feature = "std")]
+    pub fn tick_frame(&self, budget: core::time::Duration) -> usize {
+        match self {
+            Self::Enabled { executor, .. } => executor.tick_frame(budget),
+            Self::Disabled => 0,
+        }
+    }
 }
@@ -0,0 +1,299 @@
+//! the threads running the closures passed to `Executor::spawn_blocking`.
+//!
+//! threads are only spawned when a closure comes in and every thread is busy,
+//! up to a maximum, and exit after sitting idle for a while
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use yage_task::task::Task;
+
+use crate::driver::DriverCell;
+
+/// how many threads the pool spawns at most, unless told otherwise
+const DEFAULT_MAX_THREADS: usize = 512;
+/// how long a thread waits for a new closure before exiting, unless told otherwise
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) struct BlockingPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    shared: Mutex<Shared>,
+    condvar: Condvar,
+    driver: DriverCell,
+}
+
+struct Shared {
+    queue: VecDeque<Task<usize>>,
+    max_threads: usize,
+    idle_timeout: Duration,
+    num_threads: usize,
+    /// the threads waiting for a closure
+    num_idle: usize,
+    /// how many idle threads have been woken up for a closure, and taken off `num_idle` for it.
+    /// whichever thread wakes up first takes the notification, so a thread that times out
+    /// takes itself off instead
+    num_notify: usize,
+    next_id: usize,
+    threads: HashMap<usize, JoinHandle<()>>,
+    shutdown: bool,
+}
+
+impl BlockingPool {
+    pub(crate) fn new(driver: &DriverCell) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                shared: Mutex::new(Shared {
+                    queue: VecDeque::new(),
+                    max_threads: DEFAULT_MAX_THREADS,
+                    idle_timeout: DEFAULT_IDLE_TIMEOUT,
+                    num_threads: 0,
+                    num_idle: 0,
+                    num_notify: 0,
+                    next_id: 0,
+                    threads: HashMap::new(),
+                    shutdown: false,
+                }),
+                condvar: Condvar::new(),
+                driver: driver.clone(),
+            }),
+        }
+    }
+
+    /// limits the pool to `max_threads`, which exit after `idle_timeout` without anything to do.
+    /// threads beyond the limit that are already running exit once they're idle
+    pub(crate) fn configure(&self, max_threads: usize, idle_timeout: Duration) {
+        let mut shared = self.inner.shared.lock().unwrap();
+        shared.max_threads = max_threads;
+        shared.idle_timeout = idle_timeout;
+    }
+
+    /// queues `task` to be run by one of the threads, spawning one if they are all busy.
+    /// hands it back if the pool has been shut down
+    pub(crate) fn schedule(&self, task: Task<usize>) -> Result<(), Task<usize>> {
+        let mut shared = self.inner.shared.lock().unwrap();
+        if shared.shutdown {
+            return Err(task);
+        }
+        shared.queue.push_back(task);
+
+        if shared.num_idle > 0 {
+            shared.num_idle -= 1;
+            shared.num_notify += 1;
+            self.inner.condvar.notify_one();
+        } else if shared.num_threads < shared.max_threads {
+            let id = shared.next_id;
+            shared.next_id += 1;
+
+            let inner = self.inner.clone();
+            let spawned = std::thread::Builder::new()
+                .name(alloc::format!("yage-blocking-{id}"))
+                .spawn(move || inner.run(id));
+            match spawned {
+                Ok(handle) => {
+                    shared.num_threads += 1;
+                    shared.threads.insert(id, handle);
+                }
+                // the task waits for one of the threads there already are
+                Err(_) if shared.num_threads > 0 => {}
+                Err(e) => panic!("failed to spawn a blocking thread: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// wakes up every idle thread, so they catch up on the signals they have to block.
+    /// the busy ones do once they're done with their closure
+    pub(crate) fn wake_idle(&self) {
+        let _shared = self.inner.shared.lock().unwrap();
+        self.inner.condvar.notify_all();
+    }
+
+    /// stops every thread once it's done with its current closure, and waits for them to exit
+    /// until `deadline`, or for as long as it takes if it's `None`.
+    /// threads that are still running a closure then are left to finish it in the background.
+    ///
+    /// the closures that haven't started yet are dropped
+    pub(crate) fn shutdown(&self, deadline: Option<Instant>) {
+        let (queue, threads) = {
+            let mut shared = self.inner.shared.lock().unwrap();
+            shared.shutdown = true;
+            self.inner.condvar.notify_all();
+            let queue = core::mem::take(&mut shared.queue);
+
+            if let Some(deadline) = deadline {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                shared = self
+                    .inner
+                    .condvar
+                    .wait_timeout_while(shared, timeout, |shared| shared.num_threads > 0)
+                    .unwrap()
+                    .0;
+            }
+            let mut threads = core::mem::take(&mut shared.threads);
+            if shared.num_threads > 0 && deadline.is_some() {
+                // past the deadline, so the threads that are still running are detached, along with the rest
+                threads.clear();
+            }
+            (queue, threads)
+        };
+        drop(queue);
+
+        for (_, thread) in threads {
+            // the executor can be dropped by a blocking closure
+            if thread.thread().id() != std::thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn num_threads(&self) -> usize {
+        self.inner.shared.lock().unwrap().num_threads
+    }
+}
+
+impl Inner {
+    /// the main loop of thread `id`
+    fn run(&self, id: usize) {
+        let mut shared = self.shared.lock().unwrap();
+
+        'main: loop {
+            self.driver.block_signals();
+            while let Some(task) = shared.queue.pop_front() {
+                drop(shared);
+                task.run();
+                shared = self.shared.lock().unwrap();
+            }
+
+            if shared.shutdown || shared.num_threads > shared.max_threads {
+                break;
+            }
+
+            shared.num_idle += 1;
+            loop {
+                let timeout = shared.idle_timeout;
+                let (guard, result) = self.condvar.wait_timeout(shared, timeout).unwrap();
+                shared = guard;
+
+                if shared.num_notify > 0 {
+                    shared.num_notify -= 1;
+                    continue 'main;
+                }
+                if shared.shutdown || result.timed_out() {
+                    shared.num_idle -= 1;
+                    break 'main;
+                }
+                // a spurious wakeup, or one from `BlockingPool::wake_idle`, so keep waiting
+                self.driver.block_signals();
+            }
+        }
+
+        shared.num_threads -= 1;
+        if shared.shutdown {
+            // `BlockingPool::shutdown` could be waiting for this
+            self.condvar.notify_all();
+        } else {
+            // nothing has to join a thread that exits on its own
+            shared.threads.remove(&id);
+        }
+    }
+}
+
+/// the future of a task spawned by `Executor::spawn_blocking`, which runs the closure the first time it's polled
+pub(crate) struct BlockingTask<F>(pub(crate) Option<F>);
+
+impl<F> Unpin for BlockingTask<F> {}
+
+impl<F: FnOnce() -> T, T> Future for BlockingTask<F> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+        let f = self
+            .0
+            .take()
+            .expect("a blocking task was polled after it completed");
+        Poll::Ready(f())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Executor;
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use std::vec::Vec;
+
+    #[test]
+    fn closures_run_on_the_pool() {
+        let executor = Executor::new_unsync();
+        let handle = executor.spawn_blocking(|| std::thread::current().name().map(String::from));
+
+        let name = executor.block_on(handle).unwrap();
+        assert!(name.starts_with("yage-blocking-"));
+    }
+
+    #[test]
+    fn threads_are_spawned_up_to_the_max() {
+        let executor = Executor::new_sync().with_blocking_pool(2, Duration::from_secs(10));
+        let threads = Arc::new(Mutex::new(HashSet::new()));
+
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let threads = threads.clone();
+                executor.spawn_blocking(move || {
+                    std::thread::sleep(Duration::from_millis(10));
+                    threads.lock().unwrap().insert(std::thread::current().id());
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(threads.lock().unwrap().len(), 2);
+        assert_eq!(executor.inner.blocking.num_threads(), 2);
+    }
+
+    #[test]
+    fn idle_threads_exit() {
+        let executor = Executor::new_unsync().with_blocking_pool(4, Duration::from_millis(10));
+        executor.spawn_blocking(|| {}).join().unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(executor.inner.blocking.num_threads(), 0);
+
+        // and the pool spawns a new one for the next closure
+        assert_eq!(executor.spawn_blocking(|| 4).join(), Some(4));
+    }
+
+    #[test]
+    fn dropping_the_executor_waits_for_running_closures() {
+        let executor = Executor::new_unsync().with_blocking_pool(1, Duration::from_secs(10));
+        let done = Arc::new(Mutex::new(Vec::new()));
+
+        for i in 0..2 {
+            let done = done.clone();
+            executor
+                .spawn_blocking(move || {
+                    std::thread::sleep(Duration::from_millis(20));
+                    done.lock().unwrap().push(i);
+                })
+                .detach();
+        }
+        // let the first one start
+        std::thread::sleep(Duration::from_millis(5));
+        drop(executor);
+
+        assert_eq!(*done.lock().unwrap(), [0]);
+    }
+}
@@ -0,0 +1,34 @@
+//! the executor running tasks on the current thread, for the things that need it
+//! without being handed the executor, like `time::sleep`
+
+use core::cell::Cell;
+
+use crate::ExecutorInner;
+
+std::thread_local! {
+    static CURRENT: Cell<*const ExecutorInner> = const { Cell::new(core::ptr::null()) };
+}
+
+/// makes `inner` the current executor until the guard is dropped
+pub(crate) struct EnterGuard {
+    prev: *const ExecutorInner,
+}
+
+pub(crate) fn enter(inner: &ExecutorInner) -> EnterGuard {
+    EnterGuard {
+        prev: CURRENT.with(|current| current.replace(inner)),
+    }
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.prev));
+    }
+}
+
+/// calls `f` with the current executor, or returns `None` if nothing is being run by one
+pub(crate) fn with_current<R>(f: impl FnOnce(&ExecutorInner) -> R) -> Option<R> {
+    let current = CURRENT.with(Cell::get);
+    // SAFETY: the executor outlives the guard that set it, which is still alive
+    unsafe { current.as_ref() }.map(f)
+}
@@ -1,22 +1,151 @@
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::ops::{BitAnd, BitOr, Sub};
+use core::pin::Pin;
+use core::task::Poll;
 use std::cell::UnsafeCell;
 use std::marker::PhantomPinned;
 use std::ptr::NonNull;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::task::{Context, Waker};
-use yage_net::Interest;
+use yage_net::{Interest, Token, event::Event};
 use yage_util::{
     atomic::Atomic,
     list::{Link, LinkedList, Pointers},
 };
 
-pub(super) struct IoInner {
+/// the readiness of an I/O source, as reported by the driver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Ready(usize);
+
+impl Ready {
+    pub(crate) const EMPTY: Self = Self(0);
+    pub(crate) const READABLE: Self = Self(0b0_0001);
+    pub(crate) const WRITABLE: Self = Self(0b0_0010);
+    pub(crate) const READ_CLOSED: Self = Self(0b0_0100);
+    pub(crate) const WRITE_CLOSED: Self = Self(0b0_1000);
+    pub(crate) const ERROR: Self = Self(0b1_0000);
+    pub(crate) const ALL: Self = Self(0b1_1111);
+
+    pub(super) fn from_event(event: &Event) -> Self {
+        let mut ready = Self::EMPTY;
+
+        if event.is_readable() {
+            ready = ready | Self::READABLE;
+        }
+        if event.is_writable() {
+            ready = ready | Self::WRITABLE;
+        }
+        if event.is_read_closed() {
+            ready = ready | Self::READ_CLOSED;
+        }
+        if event.is_write_closed() {
+            ready = ready | Self::WRITE_CLOSED;
+        }
+        if event.is_error() {
+            ready = ready | Self::ERROR;
+        }
+
+        ready
+    }
+
+    /// the readiness that `interest` is waiting for.
+    /// errors are always included, so they aren't missed
+    pub(crate) fn from_interest(interest: Interest) -> Self {
+        let mut ready = Self::ERROR;
+        if interest.is_readable() || interest.is_priority() {
+            ready = ready | Self::READABLE | Self::READ_CLOSED;
+        }
+        if interest.is_writable() {
+            ready = ready | Self::WRITABLE | Self::WRITE_CLOSED;
+        }
+        if interest.is_read_closed() {
+            ready = ready | Self::READ_CLOSED;
+        }
+        ready
+    }
+
+    pub(crate) fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Ready {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Ready {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Sub for Ready {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+/// which of the two wakers of an `IoInner` a poll registers with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+impl Direction {
+    fn mask(self) -> Ready {
+        match self {
+            Direction::Read => Ready::READABLE | Ready::READ_CLOSED | Ready::ERROR,
+            Direction::Write => Ready::WRITABLE | Ready::WRITE_CLOSED | Ready::ERROR,
+        }
+    }
+}
+
+/// a snapshot of the readiness of a source, taken at driver tick `tick`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ReadyEvent {
+    pub(crate) tick: u8,
+    pub(crate) ready: Ready,
+    pub(crate) is_shutdown: bool,
+}
+
+/// how `IoInner::set_readiness` treats the tick
+pub(super) enum Tick {
+    /// the driver saw new events at this tick
+    Set(u8),
+    /// only clear the readiness if nothing new came in since this tick
+    Clear(u8),
+}
+
+// `readiness` is packed as `| shutdown: 1 | tick: 8 | ready: 16 |`
+const READY_MASK: usize = 0xffff;
+const TICK_SHIFT: u32 = 16;
+const TICK_MASK: usize = 0xff << TICK_SHIFT;
+const SHUTDOWN: usize = 1 << 24;
+
+/// the state the driver shares with everything waiting on a single I/O source
+pub(crate) struct IoInner {
     pointers: UnsafeCell<Pointers<Self>>,
     readiness: AtomicUsize,
     waiters: Atomic<Waiters>,
 }
 
-pub(super) struct Io(Arc<IoInner>);
+// SAFETY: `pointers` is only touched while `Synced` is locked,
+// and everything in `waiters` is only touched while it's borrowed
+unsafe impl Send for IoInner {}
+unsafe impl Sync for IoInner {}
+
+pub(super) struct Io(pub(super) Arc<IoInner>);
 
 unsafe impl Link for Io {
     type Handle = Arc<IoInner>;
@@ -71,3 +200,243 @@ unsafe impl Link for Waiter {
         unsafe { Waiter::address_of_pointers(target) }
     }
 }
+
+impl IoInner {
+    pub(super) fn new() -> Self {
+        Self {
+            pointers: UnsafeCell::new(Pointers::new()),
+            readiness: AtomicUsize::new(0),
+            waiters: Atomic::new(Waiters {
+                list: LinkedList::new(),
+                reader: None,
+                writer: None,
+            }),
+        }
+    }
+
+    /// the token this source is registered with, which is its address
+    pub(super) fn token(&self) -> Token {
+        Token(self as *const Self as usize)
+    }
+
+    /// updates the readiness with `f`.
+    /// with `Tick::Clear`, nothing happens if the driver ticked since the readiness was read
+    pub(super) fn set_readiness(&self, tick: Tick, f: impl Fn(Ready) -> Ready) {
+        let mut curr = self.readiness.load(Ordering::Acquire);
+        loop {
+            let curr_tick = ((curr & TICK_MASK) >> TICK_SHIFT) as u8;
+            let new_tick = match tick {
+                Tick::Set(t) => t,
+                Tick::Clear(t) if t != curr_tick => return,
+                Tick::Clear(t) => t,
+            };
+
+            let ready = f(Ready(curr & READY_MASK));
+            let next = (curr & SHUTDOWN) | ((new_tick as usize) << TICK_SHIFT) | ready.0;
+
+            match self.readiness.compare_exchange_weak(
+                curr,
+                next,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return,
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+
+    /// wakes everything waiting for any of `ready`
+    pub(super) fn wake(&self, ready: Ready) {
+        let mut wakers = Vec::new();
+
+        {
+            let mut waiters = self.waiters.borrow_mut();
+
+            if !(ready & Direction::Read.mask()).is_empty() {
+                wakers.extend(waiters.reader.take());
+            }
+            if !(ready & Direction::Write.mask()).is_empty() {
+                wakers.extend(waiters.writer.take());
+            }
+
+            let mut pending = LinkedList::new();
+            while let Some(mut waiter) = waiters.list.pop_front() {
+                // SAFETY: waiters are only touched with `waiters` borrowed,
+                // and they remove themselves from the list before they go away
+                let waiter_ref = unsafe { waiter.as_mut() };
+                if (Ready::from_interest(waiter_ref.interests) & ready).is_empty() {
+                    pending.push_back(waiter);
+                } else {
+                    waiter_ref.ready = true;
+                    wakers.extend(waiter_ref.waker.take());
+                }
+            }
+            waiters.list = pending;
+        }
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// marks the source as shut down, waking everything that is waiting on it
+    pub(super) fn shutdown(&self) {
+        self.readiness.fetch_or(SHUTDOWN, Ordering::AcqRel);
+        self.wake(Ready::ALL);
+    }
+
+    fn ready_event(&self, mask: Ready) -> ReadyEvent {
+        let curr = self.readiness.load(Ordering::Acquire);
+        ReadyEvent {
+            tick: ((curr & TICK_MASK) >> TICK_SHIFT) as u8,
+            ready: Ready(curr & READY_MASK) & mask,
+            is_shutdown: curr & SHUTDOWN != 0,
+        }
+    }
+
+    /// returns the readiness for `direction`, or registers `cx` to be woken once there is some.
+    ///
+    /// only the last waker to poll each direction is kept
+    pub(crate) fn poll_readiness(
+        &self,
+        cx: &mut Context<'_>,
+        direction: Direction,
+    ) -> Poll<ReadyEvent> {
+        let event = self.ready_event(direction.mask());
+        if !event.ready.is_empty() || event.is_shutdown {
+            return Poll::Ready(event);
+        }
+
+        let mut waiters = self.waiters.borrow_mut();
+        let slot = match direction {
+            Direction::Read => &mut waiters.reader,
+            Direction::Write => &mut waiters.writer,
+        };
+        match slot {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => *slot = Some(cx.waker().clone()),
+        }
+
+        // the driver could have woken the old waker before we swapped it out
+        let event = self.ready_event(direction.mask());
+        if !event.ready.is_empty() || event.is_shutdown {
+            Poll::Ready(event)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// clears the readiness in `event`, once it turned out to be stale (the source returned `WouldBlock`).
+    /// being closed is final, so that is never cleared
+    pub(crate) fn clear_readiness(&self, event: ReadyEvent) {
+        let cleared = event.ready - Ready::READ_CLOSED - Ready::WRITE_CLOSED;
+        self.set_readiness(Tick::Clear(event.tick), |curr| curr - cleared);
+    }
+
+    /// waits until the source is ready for any of `interest`.
+    ///
+    /// unlike `IoInner::poll_readiness`, any number of these can wait at the same time
+    pub(crate) fn readiness(&self, interest: Interest) -> Readiness<'_> {
+        Readiness {
+            io: self,
+            state: State::Init,
+            waiter: UnsafeCell::new(Waiter {
+                pointers: Pointers::new(),
+                waker: None,
+                interests: interest,
+                ready: false,
+                _pin: PhantomPinned,
+            }),
+        }
+    }
+}
+
+enum State {
+    Init,
+    Waiting,
+    Done,
+}
+
+/// the future returned by `IoInner::readiness`
+pub(crate) struct Readiness<'a> {
+    io: &'a IoInner,
+    state: State,
+    waiter: UnsafeCell<Waiter>,
+}
+
+// SAFETY: the waiter is only touched with `waiters` borrowed
+unsafe impl Send for Readiness<'_> {}
+unsafe impl Sync for Readiness<'_> {}
+
+impl Future for Readiness<'_> {
+    type Output = ReadyEvent;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ReadyEvent> {
+        // SAFETY: the waiter is never moved out of
+        let this = unsafe { self.get_unchecked_mut() };
+        let mask = Ready::from_interest(unsafe { (*this.waiter.get()).interests });
+
+        loop {
+            match this.state {
+                State::Init => {
+                    let event = this.io.ready_event(mask);
+                    if !event.ready.is_empty() || event.is_shutdown {
+                        this.state = State::Done;
+                        continue;
+                    }
+
+                    let mut waiters = this.io.waiters.borrow_mut();
+
+                    // the driver sets the readiness before waking, so check again now that it can't wake
+                    let event = this.io.ready_event(mask);
+                    if !event.ready.is_empty() || event.is_shutdown {
+                        this.state = State::Done;
+                        continue;
+                    }
+
+                    // SAFETY: we have `waiters` borrowed, and the future is pinned,
+                    // so the waiter stays where it is until it's removed from the list in `drop`
+                    unsafe {
+                        (*this.waiter.get()).waker = Some(cx.waker().clone());
+                        waiters
+                            .list
+                            .push_front(NonNull::new_unchecked(this.waiter.get()));
+                    }
+                    this.state = State::Waiting;
+                    return Poll::Pending;
+                }
+                State::Waiting => {
+                    let _waiters = this.io.waiters.borrow_mut();
+                    // SAFETY: we have `waiters` borrowed
+                    let waiter = unsafe { &mut *this.waiter.get() };
+
+                    if !waiter.ready {
+                        match &waiter.waker {
+                            Some(waker) if waker.will_wake(cx.waker()) => {}
+                            _ => waiter.waker = Some(cx.waker().clone()),
+                        }
+                        return Poll::Pending;
+                    }
+
+                    this.state = State::Done;
+                }
+                State::Done => return Poll::Ready(this.io.ready_event(mask)),
+            }
+        }
+    }
+}
+
+impl Drop for Readiness<'_> {
+    fn drop(&mut self) {
+        if let State::Waiting = self.state {
+            let mut waiters = self.io.waiters.borrow_mut();
+            // SAFETY: the waiter is either in this list, or was taken out of it when it was woken
+            unsafe {
+                waiters
+                    .list
+                    .remove(NonNull::new_unchecked(self.waiter.get()));
+            }
+        }
+    }
+}
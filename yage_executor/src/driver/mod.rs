@@ -1,5 +1,535 @@
-pub struct IoDriver {}
+//! the I/O driver, which waits for readiness events from the OS and wakes the tasks waiting on them.
+//! it also keeps the timers, and never waits past the next one.
+//!
+//! it is driven either by a dedicated reactor thread (see `Executor::spawn_reactor`),
+//! or inline, by whichever thread of the executor runs out of tasks first
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use yage_net::{Interest, notifier::Notifier};
+
+use self::io::{Direction, IoInner, ReadyEvent};
+use self::net::{NetDriver, NetHandle};
+use self::signal::Signals;
+use self::time::{TimeDriver, TimerShared};
+use crate::coop;
+use crate::metrics::{AtomicHistogram, Histogram};
+use crate::signal::SignalKind;
+use crate::time::Clock;
 
 mod io;
 mod net;
 mod registrations;
+pub(crate) mod signal;
+pub(crate) mod time;
+mod wheel;
+
+/// how many events are read from the selector at once
+const EVENTS_CAPACITY: usize = 1024;
+
+/// the driver of an executor, which every parker of the executor shares.
+/// it's only created once something needs it
+#[derive(Clone, Default)]
+pub(crate) struct DriverCell {
+    cell: Arc<OnceLock<Arc<IoDriver>>>,
+    clock: Clock,
+}
+
+impl DriverCell {
+    pub(crate) fn new(clock: Clock) -> Self {
+        Self {
+            cell: Arc::default(),
+            clock,
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<&Arc<IoDriver>> {
+        self.cell.get()
+    }
+
+    pub(crate) fn get_or_init(&self) -> std::io::Result<&Arc<IoDriver>> {
+        if let Some(driver) = self.cell.get() {
+            return Ok(driver);
+        }
+
+        let driver = Arc::new(IoDriver::new(self.clock.clone())?);
+        // if another thread beat us to it, ours is just dropped
+        if self.cell.set(driver.clone()).is_ok() {
+            self.clock.attach(&driver);
+        }
+        Ok(self.cell.get().unwrap())
+    }
+
+    /// the clock the timers of the driver go by
+    pub(crate) fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// blocks the signals the driver listens for on the calling thread, see `Signals::block_listened`
+    pub(crate) fn block_signals(&self) {
+        if let Some(driver) = self.get() {
+            driver.signals().block_listened();
+        }
+    }
+}
+
+pub(crate) struct IoDriver {
+    driver: Mutex<NetDriver>,
+    handle: NetHandle,
+    time: TimeDriver,
+    /// set while a thread parked in `IoDriver::park` is waiting for events,
+    /// so unparking it has to go through the selector
+    driving: AtomicBool,
+    shutdown: AtomicBool,
+    events_per_tick: AtomicHistogram,
+}
+
+impl IoDriver {
+    pub(crate) fn new(clock: Clock) -> std::io::Result<Self> {
+        let (driver, handle) = NetDriver::new(EVENTS_CAPACITY)?;
+        Ok(Self {
+            driver: Mutex::new(driver),
+            handle,
+            time: TimeDriver::new(clock),
+            driving: AtomicBool::new(false),
+            shutdown: AtomicBool::new(false),
+            events_per_tick: AtomicHistogram::new(),
+        })
+    }
+
+    /// waits for I/O events or the next timer until `notified` is set, instead of parking the thread.
+    /// it waits for `max_wait` at most, or forever if it's `None`.
+    ///
+    /// returns `false` without doing anything if another thread is already driving, or the driver was shut down.
+    /// this can also return once an I/O event came in or a timer fired, before anything set `notified`
+    pub(crate) fn park(&self, notified: &AtomicBool, max_wait: Option<Duration>) -> bool {
+        if self.shutdown.load(Ordering::Acquire) {
+            return false;
+        }
+        let Ok(mut driver) = self.driver.try_lock() else {
+            return false;
+        };
+
+        // pairs with `IoDriver::unpark`: either it sees that we are driving, or we see `notified`
+        self.driving.store(true, Ordering::SeqCst);
+        if !notified.load(Ordering::SeqCst) {
+            let timeout = match (self.time.next_timeout(), max_wait) {
+                (Some(timer), Some(max_wait)) => Some(timer.min(max_wait)),
+                (timeout, None) | (None, timeout) => timeout,
+            };
+            let events = driver.drive(&self.handle, timeout);
+            self.events_per_tick.record(events as u64);
+        }
+        self.driving.store(false, Ordering::SeqCst);
+        self.time.process();
+
+        notified.store(false, Ordering::Release);
+        true
+    }
+
+    /// polls for I/O events and fires due timers without waiting, if no other thread is driving.
+    /// returns whether it got to poll
+    pub(crate) fn poll(&self) -> bool {
+        match self.driver.try_lock() {
+            Ok(mut driver) => {
+                let events = driver.drive(&self.handle, Some(Duration::ZERO));
+                self.events_per_tick.record(events as u64);
+                self.time.process();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// wakes up the thread in `IoDriver::park`, if there is one
+    pub(crate) fn unpark(&self) {
+        if self.driving.load(Ordering::SeqCst) {
+            self.handle.unpark();
+        }
+    }
+
+    /// the main loop of the reactor thread
+    pub(crate) fn run(&self) {
+        loop {
+            let mut driver = self.driver.lock().unwrap();
+            // checked with the lock held, since a worker that drove while this waited for it
+            // could have taken the wakeup from `IoDriver::shutdown`
+            if self.shutdown.load(Ordering::Acquire) {
+                return;
+            }
+            self.handle.signals.block_listened();
+            let events = driver.drive(&self.handle, self.time.next_timeout());
+            self.events_per_tick.record(events as u64);
+            self.time.process();
+        }
+    }
+
+    /// (re)registers `timer` to fire at `deadline`
+    pub(crate) fn register_timer(&self, timer: &Arc<TimerShared>, deadline: Instant) {
+        if self.time.register(timer, deadline) {
+            // whoever is driving is going to wait past the deadline
+            self.handle.unpark();
+        }
+    }
+
+    /// takes `timer` out of the wheel, so it doesn't take up space there until its deadline
+    pub(crate) fn deregister_timer(&self, timer: &Arc<TimerShared>) {
+        self.time.deregister(timer);
+    }
+
+    /// how many timers are in the wheel
+    #[cfg(test)]
+    pub(crate) fn num_timers(&self) -> usize {
+        self.time.num_entries()
+    }
+
+    /// fires every timer that is due, without waiting for the thread driving this
+    pub(crate) fn process_timers(&self) {
+        self.time.process();
+    }
+
+    /// starts listening for `kind`, blocking it on the calling thread.
+    /// returns whether it wasn't listened for before, see `Signals::add`
+    pub(crate) fn add_signal(&self, kind: SignalKind) -> std::io::Result<bool> {
+        if self.shutdown.load(Ordering::Acquire) {
+            return Err(registrations::shutdown_error());
+        }
+        let added = self.handle.add_signal(kind)?;
+        if added {
+            // so the reactor thread blocks it as well
+            self.handle.unpark();
+        }
+        Ok(added)
+    }
+
+    pub(crate) fn signals(&self) -> &Signals {
+        &self.handle.signals
+    }
+
+    /// how many events every poll of the selector got
+    pub(crate) fn events_per_tick(&self) -> Histogram {
+        self.events_per_tick.snapshot()
+    }
+
+    /// stops the reactor thread, and wakes everything waiting on I/O.
+    /// anything waiting on a source after this gets an error
+    pub(crate) fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        self.handle.unpark();
+        self.handle.shutdown();
+    }
+}
+
+/// a source registered with an `IoDriver`.
+///
+/// the driver keeps the state shared with it alive until it's deregistered,
+/// so dropping this without calling `Registration::deregister` leaks that until the driver is dropped
+pub(crate) struct Registration {
+    driver: Arc<IoDriver>,
+    shared: Arc<IoInner>,
+}
+
+impl Registration {
+    pub(crate) fn new(
+        driver: &Arc<IoDriver>,
+        source: &mut (impl Notifier + ?Sized),
+        interest: Interest,
+    ) -> std::io::Result<Self> {
+        let shared = driver.handle.add_source(source, interest)?;
+        Ok(Self {
+            driver: driver.clone(),
+            shared,
+        })
+    }
+
+    /// polls for read readiness, see `IoInner::poll_readiness`
+    pub(crate) fn poll_read_ready(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<ReadyEvent>> {
+        self.poll_ready(cx, Direction::Read)
+    }
+
+    /// polls for write readiness, see `IoInner::poll_readiness`
+    pub(crate) fn poll_write_ready(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<ReadyEvent>> {
+        self.poll_ready(cx, Direction::Write)
+    }
+
+    /// calls `f` once the source is readable, until it stops returning `WouldBlock`
+    pub(crate) fn poll_read_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        f: impl FnMut() -> std::io::Result<R>,
+    ) -> Poll<std::io::Result<R>> {
+        self.poll_io(cx, Direction::Read, f)
+    }
+
+    /// calls `f` once the source is writable, until it stops returning `WouldBlock`
+    pub(crate) fn poll_write_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        f: impl FnMut() -> std::io::Result<R>,
+    ) -> Poll<std::io::Result<R>> {
+        self.poll_io(cx, Direction::Write, f)
+    }
+
+    /// every `WouldBlock` clears the readiness `f` was called with, so the next poll waits for the driver
+    fn poll_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        direction: Direction,
+        mut f: impl FnMut() -> std::io::Result<R>,
+    ) -> Poll<std::io::Result<R>> {
+        loop {
+            let event = core::task::ready!(self.poll_ready(cx, direction))?;
+            match f() {
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => self.clear_readiness(event),
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    /// calls `f` once the source is ready for any of `interest`, until it stops returning `WouldBlock`.
+    /// unlike `Registration::poll_read_io`, any number of tasks can wait on this at once
+    pub(crate) async fn async_io<R>(
+        &self,
+        interest: Interest,
+        mut f: impl FnMut() -> std::io::Result<R>,
+    ) -> std::io::Result<R> {
+        loop {
+            let event = self.readiness(interest).await?;
+            match f() {
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => self.clear_readiness(event),
+                result => return result,
+            }
+        }
+    }
+
+    fn poll_ready(
+        &self,
+        cx: &mut Context<'_>,
+        direction: Direction,
+    ) -> Poll<std::io::Result<ReadyEvent>> {
+        core::task::ready!(coop::poll_proceed(cx));
+        self.shared
+            .poll_readiness(cx, direction)
+            .map(Self::check_shutdown)
+    }
+
+    /// waits until the source is ready for any of `interest`
+    pub(crate) async fn readiness(&self, interest: Interest) -> std::io::Result<ReadyEvent> {
+        coop::consume_budget().await;
+        Self::check_shutdown(self.shared.readiness(interest).await)
+    }
+
+    /// forgets the readiness in `event`, after the source returned `WouldBlock`
+    pub(crate) fn clear_readiness(&self, event: ReadyEvent) {
+        self.shared.clear_readiness(event);
+    }
+
+    /// deregisters `source`, which has to be the source this was registered with
+    pub(crate) fn deregister(&self, source: &mut (impl Notifier + ?Sized)) -> std::io::Result<()> {
+        self.driver.handle.deregister_source(&self.shared, source)
+    }
+
+    fn check_shutdown(event: ReadyEvent) -> std::io::Result<ReadyEvent> {
+        if event.is_shutdown {
+            Err(registrations::shutdown_error())
+        } else {
+            Ok(event)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Executor;
+    use std::io::{self, Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+    use yage_net::notifier::SourceFd;
+
+    fn pair() -> (UnixStream, UnixStream) {
+        let (a, b) = UnixStream::pair().unwrap();
+        a.set_nonblocking(true).unwrap();
+        (a, b)
+    }
+
+    /// reads from `stream` once it's readable, clearing the readiness on `WouldBlock`
+    async fn read(registration: &Registration, mut stream: &UnixStream) -> io::Result<u8> {
+        loop {
+            let event = registration.readiness(Interest::READABLE).await?;
+            let mut buf = [0];
+            match stream.read(&mut buf) {
+                Ok(_) => return Ok(buf[0]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    registration.clear_readiness(event)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[test]
+    fn block_on_drives_io_when_idle() {
+        let executor = Executor::new_unsync();
+        let driver = executor.io_driver().unwrap();
+        let (a, mut b) = pair();
+        let registration =
+            Registration::new(&driver, &mut SourceFd(&a.as_raw_fd()), Interest::READABLE).unwrap();
+
+        let handle = executor.spawn(async move {
+            let byte = read(&registration, &a).await;
+            registration
+                .deregister(&mut SourceFd(&a.as_raw_fd()))
+                .unwrap();
+            byte
+        });
+
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            b.write_all(&[7]).unwrap();
+        });
+
+        assert_eq!(executor.block_on(handle).unwrap(), 7);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn io_yields_once_the_budget_runs_out() {
+        let executor = Executor::new_unsync();
+        let driver = executor.io_driver().unwrap();
+        let (a, mut b) = pair();
+        b.write_all(&[0; 1024]).unwrap();
+        let registration =
+            Registration::new(&driver, &mut SourceFd(&a.as_raw_fd()), Interest::READABLE).unwrap();
+
+        let reads = std::rc::Rc::new(core::cell::Cell::new(0));
+        let counter = reads.clone();
+        executor
+            .spawn(async move {
+                loop {
+                    read(&registration, &a).await.unwrap();
+                    counter.set(counter.get() + 1);
+                }
+            })
+            .detach();
+
+        // the first poll finds the source not ready yet, and the second one is woken by the driver
+        assert!(executor.tick());
+        assert!(executor.tick());
+        let before = reads.get();
+        assert!(executor.tick());
+        assert_eq!(reads.get() - before, coop::DEFAULT_BUDGET as usize);
+    }
+
+    #[test]
+    fn reactor_thread_wakes_tasks() {
+        let mut executor = Executor::with_workers(1);
+        executor.spawn_reactor().unwrap();
+        let driver = executor.io_driver().unwrap();
+
+        let (a, mut b) = pair();
+        let registration =
+            Registration::new(&driver, &mut SourceFd(&a.as_raw_fd()), Interest::READABLE).unwrap();
+
+        let handle = executor.spawn(async move {
+            let first = read(&registration, &a).await.unwrap();
+            let second = read(&registration, &a).await.unwrap();
+            registration
+                .deregister(&mut SourceFd(&a.as_raw_fd()))
+                .unwrap();
+            (first, second)
+        });
+
+        b.write_all(&[1]).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        b.write_all(&[2]).unwrap();
+
+        // nothing here drives I/O, so this only finishes if the reactor thread does
+        assert_eq!(handle.join(), Some((1, 2)));
+    }
+
+    #[test]
+    fn deregistered_sources_are_released_on_the_next_poll() {
+        let driver = Arc::new(IoDriver::new(Clock::System).unwrap());
+        let (a, _b) = pair();
+        let registration =
+            Registration::new(&driver, &mut SourceFd(&a.as_raw_fd()), Interest::READABLE).unwrap();
+        let shared = Arc::downgrade(&registration.shared);
+
+        registration
+            .deregister(&mut SourceFd(&a.as_raw_fd()))
+            .unwrap();
+        drop(registration);
+        assert_eq!(driver.handle.num_pending_release(), 1);
+        assert!(shared.upgrade().is_some());
+
+        assert!(driver.poll());
+        assert_eq!(driver.handle.num_pending_release(), 0);
+        assert!(shared.upgrade().is_none());
+    }
+
+    #[test]
+    fn executor_shutdown_releases_sources() {
+        let executor = Executor::new_unsync();
+        let driver = executor.io_driver().unwrap();
+        let (a, _b) = pair();
+        let registration =
+            Registration::new(&driver, &mut SourceFd(&a.as_raw_fd()), Interest::READABLE).unwrap();
+
+        let handle = executor.spawn(async move { read(&registration, &a).await });
+        executor.run_until_stalled();
+
+        executor.shutdown(Duration::from_millis(10));
+        assert!(handle.join().is_none());
+
+        let (c, _d) = pair();
+        assert!(
+            Registration::new(&driver, &mut SourceFd(&c.as_raw_fd()), Interest::READABLE).is_err()
+        );
+    }
+
+    #[test]
+    fn shutdown_wakes_waiting_tasks() {
+        let executor = Executor::new_unsync();
+        let driver = executor.io_driver().unwrap();
+        let (a, _b) = pair();
+        let registration =
+            Registration::new(&driver, &mut SourceFd(&a.as_raw_fd()), Interest::READABLE).unwrap();
+
+        let handle = executor.spawn(async move { read(&registration, &a).await });
+        assert_eq!(executor.run_until_stalled(), 1);
+
+        driver.shutdown();
+        assert!(executor.block_on(handle).is_err());
+    }
+
+    #[test]
+    fn shutdown_keeps_sources_alive_until_the_driver_is_dropped() {
+        let executor = Executor::new_unsync();
+        let driver = executor.io_driver().unwrap();
+        let (a, _b) = pair();
+        let registration =
+            Registration::new(&driver, &mut SourceFd(&a.as_raw_fd()), Interest::READABLE).unwrap();
+        let shared = Arc::downgrade(&registration.shared);
+
+        // never deregistered, so the selector can still have events for it
+        drop(registration);
+        driver.shutdown();
+        assert!(shared.upgrade().is_some());
+
+        drop(driver);
+        drop(executor);
+        assert!(shared.upgrade().is_none());
+    }
+}
@@ -1,10 +1,15 @@
+use crate::driver::io::{IoInner, Ready, Tick};
+use crate::driver::signal::Signals;
 use crate::driver::{registrations::Registrations, registrations::Synced};
+use crate::signal::SignalKind;
+use alloc::sync::Arc;
 use std::io;
 use std::sync::Mutex;
 use std::time::Duration;
 use yage_net::{
-    Token,
-    event_loop::{EventLoop, Events, Registry},
+    Interest, Token, Trigger,
+    event::Events,
+    event_loop::{EventLoop, Registry},
     notifier::Notifier,
     waker::IoWaker,
 };
@@ -17,10 +22,13 @@ pub(crate) struct NetHandle {
     registrations: Registrations,
     synced: Mutex<Synced>,
     waker: IoWaker,
+    pub(crate) signals: Signals,
 }
 
 pub(crate) struct NetDriver {
     signal_ready: bool,
+    /// bumped on every poll, so stale readiness isn't cleared over new events
+    tick: u8,
     events: Events,
     event_loop: EventLoop,
 }
@@ -33,6 +41,7 @@ impl NetDriver {
 
         let driver = NetDriver {
             signal_ready: false,
+            tick: 0,
             events: Events::with_capacity(nevents),
             event_loop: e_loop,
         };
@@ -44,10 +53,122 @@ impl NetDriver {
             registrations,
             synced: Mutex::new(synced),
             waker,
+            signals: Signals::new(),
         };
 
         Ok((driver, handle))
     }
 
-    fn drive(&mut self, handle: &NetHandle, max_wait: Option<Duration>) {}
+    /// waits for I/O events for at most `max_wait`, forever if it's `None`,
+    /// and wakes everything that is waiting on the sources they are for.
+    /// returns how many events there were
+    pub(crate) fn drive(&mut self, handle: &NetHandle, max_wait: Option<Duration>) -> usize {
+        if handle.registrations.needs_release() {
+            handle
+                .registrations
+                .release(&mut handle.synced.lock().unwrap());
+        }
+
+        self.tick = self.tick.wrapping_add(1);
+
+        match self.event_loop.poll(&mut self.events, max_wait) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return 0,
+            Err(e) => panic!("unexpected error while polling for I/O events: {e}"),
+        }
+
+        for event in &self.events {
+            let token = event.token();
+
+            if token == TOKEN_WAKEUP {
+                // only here to interrupt the poll
+            } else if token == TOKEN_SIGNAL {
+                self.signal_ready = true;
+            } else {
+                let ready = Ready::from_event(event);
+
+                // SAFETY: the token of every source is the address of its `IoInner`,
+                // which `Registrations` keeps alive until the first poll after it was deregistered.
+                // that poll can't see events for it, since it was deregistered from the selector first
+                let io = unsafe { &*(token.0 as *const IoInner) };
+                io.set_readiness(Tick::Set(self.tick), |curr| curr | ready);
+                io.wake(ready);
+            }
+        }
+
+        if core::mem::take(&mut self.signal_ready) {
+            handle.signals.process();
+        }
+
+        self.events.len()
+    }
+}
+
+impl NetHandle {
+    /// registers `source` with the selector, returning the state shared with the driver.
+    /// sources are registered edge-triggered, which is what the readiness kept in `IoInner` relies on
+    pub(crate) fn add_source(
+        &self,
+        source: &mut (impl Notifier + ?Sized),
+        interest: Interest,
+    ) -> io::Result<Arc<IoInner>> {
+        let io = self
+            .registrations
+            .allocate(&mut self.synced.lock().unwrap())?;
+
+        if let Err(e) = self
+            .registry
+            .register(source, io.token(), interest, Trigger::Edge)
+        {
+            self.registrations
+                .remove(&mut self.synced.lock().unwrap(), &io);
+            return Err(e);
+        }
+
+        Ok(io)
+    }
+
+    /// deregisters `source` from the selector.
+    /// `io` is released the next time the driver polls
+    pub(crate) fn deregister_source(
+        &self,
+        io: &Arc<IoInner>,
+        source: &mut (impl Notifier + ?Sized),
+    ) -> io::Result<()> {
+        self.registry.deregister(source)?;
+
+        if self
+            .registrations
+            .deregister(&mut self.synced.lock().unwrap(), io)
+        {
+            self.unpark();
+        }
+        Ok(())
+    }
+
+    /// starts listening for `kind`, see `Signals::add`
+    pub(crate) fn add_signal(&self, kind: SignalKind) -> io::Result<bool> {
+        self.signals.add(kind, &self.registry, TOKEN_SIGNAL)
+    }
+
+    /// interrupts the driver if it's waiting for events
+    pub(crate) fn unpark(&self) {
+        self.waker.wake().expect("failed to wake the I/O driver");
+    }
+
+    /// stops new sources from being registered, and wakes everything waiting on the current ones
+    pub(crate) fn shutdown(&self) {
+        let sources = self
+            .registrations
+            .shutdown(&mut self.synced.lock().unwrap());
+        for io in sources {
+            io.shutdown();
+        }
+        self.signals.shutdown();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn num_pending_release(&self) -> usize {
+        self.registrations.num_pending_release()
+    }
 }
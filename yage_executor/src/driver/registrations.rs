@@ -1,11 +1,16 @@
 use crate::driver::io::{Io, IoInner};
 use alloc::vec::Vec;
+use std::io;
 use std::sync::{
-    Arc, Mutex,
+    Arc,
     atomic::{AtomicUsize, Ordering},
 };
 use yage_util::list::{Link, LinkedList};
 
+/// how many sources can be waiting to be released before the driver is woken up to release them
+const NOTIFY_AFTER: usize = 16;
+
+/// keeps every registered source alive until the driver can no longer see events for it
 pub(super) struct Registrations {
     num_pending_release: AtomicUsize,
 }
@@ -19,14 +24,95 @@ impl Registrations {
         let synced = Synced {
             shutdown: false,
             registrations: LinkedList::new(),
-            pending_drop: Vec::with_capacity(16),
+            pending_drop: Vec::with_capacity(NOTIFY_AFTER),
+            shut_down: Vec::new(),
         };
         (this, synced)
     }
+
+    /// creates the shared state for a new source
+    pub(super) fn allocate(&self, synced: &mut Synced) -> io::Result<Arc<IoInner>> {
+        if synced.shutdown {
+            return Err(shutdown_error());
+        }
+
+        let io = Arc::new(IoInner::new());
+        synced.registrations.push_front(io.clone());
+        Ok(io)
+    }
+
+    /// queues `io` to be released once the driver is done with the events it has already seen.
+    /// returns `true` if the driver should be woken up to release it
+    pub(super) fn deregister(&self, synced: &mut Synced, io: &Arc<IoInner>) -> bool {
+        if synced.shutdown {
+            // it's no longer in `registrations`, and is kept alive by `shut_down` instead
+            return false;
+        }
+        synced.pending_drop.push(Io(io.clone()));
+        let len = synced.pending_drop.len();
+        self.num_pending_release.store(len, Ordering::Release);
+        len == NOTIFY_AFTER
+    }
+
+    pub(super) fn needs_release(&self) -> bool {
+        self.num_pending_release.load(Ordering::Acquire) != 0
+    }
+
+    /// frees everything that was deregistered
+    pub(super) fn release(&self, synced: &mut Synced) {
+        for Io(io) in synced.pending_drop.drain(..) {
+            // SAFETY: everything in `pending_drop` is still in `registrations`, until right now
+            unsafe {
+                synced.registrations.remove(Io::as_raw(&io));
+            }
+        }
+        self.num_pending_release.store(0, Ordering::Release);
+    }
+
+    /// removes a source that never got registered with the selector
+    pub(super) fn remove(&self, synced: &mut Synced, io: &Arc<IoInner>) {
+        // SAFETY: `io` was allocated in `registrations`
+        unsafe {
+            synced.registrations.remove(Io::as_raw(io));
+        }
+    }
+
+    /// stops any new sources from being registered, returning the ones that still are.
+    ///
+    /// those are kept alive until the driver is dropped, since a thread driving it
+    /// can still be looking at events for them, or get some for the ones that were never deregistered
+    pub(super) fn shutdown(&self, synced: &mut Synced) -> Vec<Arc<IoInner>> {
+        if synced.shutdown {
+            return Vec::new();
+        }
+        synced.shutdown = true;
+        // everything in here is in `registrations` as well
+        synced.pending_drop.clear();
+        self.num_pending_release.store(0, Ordering::Release);
+
+        while let Some(io) = synced.registrations.pop_front() {
+            synced.shut_down.push(io);
+        }
+        synced.shut_down.clone()
+    }
+
+    #[cfg(test)]
+    pub(super) fn num_pending_release(&self) -> usize {
+        self.num_pending_release.load(Ordering::Acquire)
+    }
 }
 
 pub(super) struct Synced {
     shutdown: bool,
     registrations: LinkedList<Io>,
     pending_drop: Vec<Io>,
+    /// the sources that were still registered when the driver shut down
+    shut_down: Vec<Arc<IoInner>>,
+}
+
+// SAFETY: the sources in the list are only touched through the mutex `Synced` lives in
+unsafe impl Send for Synced {}
+
+pub(super) fn shutdown_error() -> io::Error {
+    io::Error::other("the I/O driver has shut down")
 }
@@ -0,0 +1,235 @@
+//! the signals the executor listens for, read from a `signalfd` registered with the selector.
+//!
+//! the fd is only created once something listens for a signal, and the signals it reads are
+//! added to its mask as they're asked for. the threads of the executor only block the signals
+//! in that mask, which they catch up on whenever they wake up, see `Signals::block_listened`
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use slab::Slab;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::Mutex;
+use yage_net::{Interest, Token, Trigger, event_loop::Registry, notifier::SourceFd};
+
+use crate::signal::SignalKind;
+
+pub(crate) struct Signals {
+    state: Mutex<State>,
+    slots: [Slot; SignalKind::ALL.len()],
+    /// bumped whenever a signal is added to the mask, so the threads of the executor know to block it
+    generation: AtomicU64,
+    shutdown: AtomicBool,
+}
+
+struct State {
+    fd: Option<OwnedFd>,
+    mask: libc::sigset_t,
+}
+
+/// the deliveries of a single kind of signal
+struct Slot {
+    /// how many times it has been received, which every listener compares against what it has seen
+    received: AtomicU64,
+    wakers: Mutex<Slab<Waker>>,
+}
+
+impl Signals {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                fd: None,
+                mask: empty_set(),
+            }),
+            slots: core::array::from_fn(|_| Slot {
+                received: AtomicU64::new(0),
+                wakers: Mutex::new(Slab::new()),
+            }),
+            generation: AtomicU64::new(0),
+            shutdown: AtomicBool::new(false),
+        }
+    }
+
+    /// starts reading `kind` from the fd, creating it and registering it as `token` if it's the first.
+    /// the signal is blocked on the calling thread, or it would never reach the fd.
+    ///
+    /// returns whether `kind` wasn't read before, in which case the other threads of the executor
+    /// have to be woken up to block it
+    pub(crate) fn add(
+        &self,
+        kind: SignalKind,
+        registry: &Registry,
+        token: Token,
+    ) -> io::Result<bool> {
+        block(kind)?;
+
+        let mut state = self.state.lock().unwrap();
+        // SAFETY: `mask` is initialized, and `kind` is a valid signal
+        if unsafe { libc::sigismember(&state.mask, kind.as_raw()) } == 1 {
+            return Ok(false);
+        }
+        let mut mask = state.mask;
+        // SAFETY: as above
+        unsafe { libc::sigaddset(&mut mask, kind.as_raw()) };
+
+        match &state.fd {
+            // SAFETY: `fd` is a signalfd, whose mask is replaced
+            Some(fd) => {
+                cvt(unsafe { libc::signalfd(fd.as_raw_fd(), &mask, 0) })?;
+            }
+            None => {
+                let flags = libc::SFD_NONBLOCK | libc::SFD_CLOEXEC;
+                // SAFETY: -1 creates a new fd, which is owned from here on
+                let fd = unsafe { OwnedFd::from_raw_fd(cvt(libc::signalfd(-1, &mask, flags))?) };
+                registry.register(
+                    &mut SourceFd(&fd.as_raw_fd()),
+                    token,
+                    Interest::READABLE,
+                    Trigger::Edge,
+                )?;
+                state.fd = Some(fd);
+            }
+        }
+        state.mask = mask;
+        self.generation.fetch_add(1, Ordering::Release);
+        Ok(true)
+    }
+
+    /// blocks every signal that is listened for on the calling thread, if it hasn't yet.
+    /// this is called by the threads the executor spawns whenever they wake up,
+    /// so the signals they would otherwise get go to the fd instead
+    pub(crate) fn block_listened(&self) {
+        let generation = self.generation.load(Ordering::Acquire);
+        if BLOCKED.get() == generation {
+            return;
+        }
+        let mask = self.state.lock().unwrap().mask;
+        // this only fails for an invalid `how`
+        let _ = block_set(&mask);
+        BLOCKED.set(generation);
+    }
+
+    /// reads every signal that came in, and wakes whatever listens for them
+    pub(crate) fn process(&self) {
+        let state = self.state.lock().unwrap();
+        let Some(fd) = &state.fd else { return };
+
+        loop {
+            // SAFETY: `signalfd_siginfo` is plain old data
+            let mut info: libc::signalfd_siginfo = unsafe { core::mem::zeroed() };
+            let size = size_of::<libc::signalfd_siginfo>();
+            // SAFETY: `info` is valid for writes of `size` bytes
+            let read = unsafe { libc::read(fd.as_raw_fd(), (&raw mut info).cast(), size) };
+            if read != size as isize {
+                // `WouldBlock` once everything has been read, and the fd is registered edge-triggered
+                break;
+            }
+
+            let kind = SignalKind::ALL
+                .into_iter()
+                .position(|kind| kind.as_raw() as u32 == info.ssi_signo);
+            if let Some(index) = kind {
+                self.slots[index].received.fetch_add(1, Ordering::Release);
+                self.wake(index);
+            }
+        }
+    }
+
+    /// how many times `kind` has been received
+    pub(crate) fn received(&self, kind: SignalKind) -> u64 {
+        self.slots[kind as usize].received.load(Ordering::Acquire)
+    }
+
+    /// returns `Ready(Some(()))` once `kind` has been received more than `seen` times, catching `seen` up.
+    /// `key` is where the waker of the listener is kept, which `Signals::remove` has to be called with
+    pub(crate) fn poll_recv(
+        &self,
+        kind: SignalKind,
+        seen: &mut u64,
+        key: &mut Option<usize>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<()>> {
+        let slot = &self.slots[kind as usize];
+        {
+            let mut wakers = slot.wakers.lock().unwrap();
+            match key.and_then(|key| wakers.get_mut(key)) {
+                Some(waker) => waker.clone_from(cx.waker()),
+                None => *key = Some(wakers.insert(cx.waker().clone())),
+            }
+        }
+
+        // checked after registering the waker, so a signal in between isn't missed
+        let received = slot.received.load(Ordering::Acquire);
+        if received > *seen {
+            *seen = received;
+            Poll::Ready(Some(()))
+        } else if self.shutdown.load(Ordering::Acquire) {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// forgets the waker of a listener that is going away
+    pub(crate) fn remove(&self, kind: SignalKind, key: usize) {
+        self.slots[kind as usize]
+            .wakers
+            .lock()
+            .unwrap()
+            .try_remove(key);
+    }
+
+    /// wakes every listener, which stop getting signals
+    pub(crate) fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        for index in 0..self.slots.len() {
+            self.wake(index);
+        }
+    }
+
+    fn wake(&self, index: usize) {
+        for (_, waker) in self.slots[index].wakers.lock().unwrap().iter() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+std::thread_local! {
+    /// the generation of the mask that was last blocked on this thread by `Signals::block_listened`.
+    /// only the threads of a single executor call that
+    static BLOCKED: Cell<u64> = const { Cell::new(0) };
+}
+
+fn empty_set() -> libc::sigset_t {
+    // SAFETY: `sigemptyset` initializes the set
+    unsafe {
+        let mut set = core::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        set
+    }
+}
+
+/// blocks `kind` on the calling thread
+fn block(kind: SignalKind) -> io::Result<()> {
+    let mut set = empty_set();
+    // SAFETY: `set` is initialized, and `kind` is a valid signal
+    unsafe { libc::sigaddset(&mut set, kind.as_raw()) };
+    block_set(&set)
+}
+
+fn block_set(set: &libc::sigset_t) -> io::Result<()> {
+    // SAFETY: `set` is initialized, and the old mask isn't asked for
+    match unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, set, core::ptr::null_mut()) } {
+        0 => Ok(()),
+        e => Err(io::Error::from_raw_os_error(e)),
+    }
+}
+
+fn cvt(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
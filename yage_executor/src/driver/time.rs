@@ -0,0 +1,203 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::task::Waker;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use yage_util::atomic::Atomic;
+
+use super::wheel::Wheel;
+use crate::time::Clock;
+
+/// the state of a timer that isn't in the wheel
+const UNREGISTERED: u64 = u64::MAX;
+/// the state of a timer that went off
+const FIRED: u64 = u64::MAX - 1;
+
+/// the state the time driver shares with a single timer
+pub(crate) struct TimerShared {
+    /// the tick the timer is registered for, or `UNREGISTERED` or `FIRED`
+    state: AtomicU64,
+    waker: Atomic<Option<Waker>>,
+    /// the level of the wheel the entry of the timer was last put in, and where in its slot,
+    /// which are only touched with the wheel locked
+    level: AtomicUsize,
+    index: AtomicUsize,
+}
+
+impl TimerShared {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: AtomicU64::new(UNREGISTERED),
+            waker: Atomic::new(None),
+            level: AtomicUsize::new(usize::MAX),
+            index: AtomicUsize::new(0),
+        }
+    }
+
+    /// the tick the timer is registered for, if it is
+    fn registered_for(&self) -> Option<u64> {
+        let state = self.state.load(Ordering::Acquire);
+        (state != UNREGISTERED && state != FIRED).then_some(state)
+    }
+
+    pub(super) fn position(&self) -> (usize, usize) {
+        (
+            self.level.load(Ordering::Relaxed),
+            self.index.load(Ordering::Relaxed),
+        )
+    }
+
+    pub(super) fn set_position(&self, level: usize, index: usize) {
+        self.level.store(level, Ordering::Relaxed);
+        self.index.store(index, Ordering::Relaxed);
+    }
+
+    pub(super) fn is_registered_for(&self, when: u64) -> bool {
+        self.state.load(Ordering::Acquire) == when
+    }
+
+    pub(super) fn set_registered(&self, when: u64) {
+        self.state.store(when, Ordering::Release);
+    }
+
+    /// marks the timer as not being in the wheel, see `TimeDriver::deregister` to take it out
+    pub(super) fn set_unregistered(&self) {
+        self.state.store(UNREGISTERED, Ordering::Release);
+    }
+
+    pub(crate) fn has_fired(&self) -> bool {
+        self.state.load(Ordering::Acquire) == FIRED
+    }
+
+    /// registers `waker` to be woken when the timer fires.
+    /// check `TimerShared::has_fired` after this, not before, so a firing in between isn't missed
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        let mut slot = self.waker.borrow_mut();
+        match &*slot {
+            Some(old) if old.will_wake(waker) => {}
+            _ => *slot = Some(waker.clone()),
+        }
+    }
+
+    /// fires the timer, unless it was reset or dropped since it was registered for `when`.
+    /// returns the waker to wake
+    fn fire(&self, when: u64) -> Option<Waker> {
+        self.state
+            .compare_exchange(when, FIRED, Ordering::AcqRel, Ordering::Acquire)
+            .ok()?;
+        self.waker.borrow_mut().take()
+    }
+}
+
+/// the timers of an executor.
+/// it counts time in milliseconds since it was created, which it calls ticks
+pub(crate) struct TimeDriver {
+    clock: Clock,
+    start: Instant,
+    wheel: Mutex<Wheel>,
+    /// the tick the thread waiting on I/O is going to wake up at,
+    /// so a timer that is due earlier can wake it up. `0` while nothing is waiting
+    next_wake: AtomicU64,
+}
+
+impl TimeDriver {
+    pub(crate) fn new(clock: Clock) -> Self {
+        Self {
+            start: clock.now(),
+            clock,
+            wheel: Mutex::new(Wheel::new()),
+            next_wake: AtomicU64::new(0),
+        }
+    }
+
+    /// the tick `instant` falls in, rounding up so timers never fire early
+    fn instant_to_tick(&self, instant: Instant) -> u64 {
+        let since = instant.saturating_duration_since(self.start);
+        let ticks = since.as_millis() + u128::from(!since.subsec_nanos().is_multiple_of(1_000_000));
+        // keep clear of `UNREGISTERED` and `FIRED`
+        ticks.min(u128::from(FIRED - 1)) as u64
+    }
+
+    /// the tick that has fully passed by now
+    fn now_tick(&self) -> u64 {
+        self.clock
+            .now()
+            .saturating_duration_since(self.start)
+            .as_millis() as u64
+    }
+
+    /// (re)registers `timer` to fire at `deadline`.
+    /// returns `true` if it fires before the driver was going to wake up, in which case the driver needs to be woken
+    pub(crate) fn register(&self, timer: &Arc<TimerShared>, deadline: Instant) -> bool {
+        let when = self.instant_to_tick(deadline);
+        let mut wheel = self.wheel.lock().unwrap();
+
+        if let Some(old) = timer.registered_for() {
+            wheel.remove(old, timer);
+        }
+        timer.set_registered(when);
+        if let Err(timer) = wheel.insert(when, timer.clone()) {
+            drop(wheel);
+            if let Some(waker) = timer.fire(when) {
+                waker.wake();
+            }
+            return false;
+        }
+
+        let next_wake = self.next_wake.load(Ordering::Acquire);
+        next_wake != 0 && when < next_wake
+    }
+
+    /// takes `timer` out of the wheel, so it never fires
+    pub(crate) fn deregister(&self, timer: &Arc<TimerShared>) {
+        let mut wheel = self.wheel.lock().unwrap();
+        if let Some(when) = timer.registered_for() {
+            wheel.remove(when, timer);
+        }
+        timer.set_unregistered();
+    }
+
+    /// how many entries the wheel holds
+    #[cfg(test)]
+    pub(crate) fn num_entries(&self) -> usize {
+        self.wheel.lock().unwrap().len()
+    }
+
+    /// how long the driver can wait for I/O before the next timer is due, `None` if there are no timers.
+    /// a mock clock only moves when it's advanced, which fires the timers itself, so that never has to wait for them.
+    ///
+    /// the driver calls this right before it waits, see `TimeDriver::register`
+    pub(super) fn next_timeout(&self) -> Option<Duration> {
+        if self.clock.is_mock() {
+            return None;
+        }
+
+        let wheel = self.wheel.lock().unwrap();
+        let Some(deadline) = wheel.next_deadline() else {
+            self.next_wake.store(u64::MAX, Ordering::Release);
+            return None;
+        };
+        self.next_wake.store(deadline.max(1), Ordering::Release);
+
+        let deadline = self.start + Duration::from_millis(deadline);
+        Some(deadline.saturating_duration_since(self.clock.now()))
+    }
+
+    /// fires every timer that is due
+    pub(super) fn process(&self) {
+        self.next_wake.store(0, Ordering::Release);
+
+        let mut expired = Vec::new();
+        self.wheel
+            .lock()
+            .unwrap()
+            .poll(self.now_tick(), &mut expired);
+
+        for (when, timer) in expired {
+            if let Some(waker) = timer.fire(when) {
+                waker.wake();
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! a hierarchical timer wheel with a resolution of one tick (a millisecond).
+//!
+//! there are `NUM_LEVELS` levels of `LEVEL_MULT` slots each. a slot on level `n` covers `LEVEL_MULT^n` ticks,
+//! so level 0 covers the next 64 ticks one by one, level 1 the next 4096 ticks 64 at a time, and so on.
+//! timers move down a level every time the slot they are in comes up, until they expire on level 0
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::time::TimerShared;
+
+const NUM_LEVELS: usize = 6;
+const LEVEL_MULT: usize = 64;
+const SLOT_MASK: u64 = LEVEL_MULT as u64 - 1;
+
+/// timers further out than this end up in the top level, and just go around it until they expire
+const MAX_DURATION: u64 = (1 << (6 * NUM_LEVELS)) - 1;
+
+/// a timer, with the tick it was registered for.
+/// timers that are reset or dropped are taken out with `Wheel::remove`, and any entry that is left over
+/// from a race with that is skipped, since its tick won't match the state of the timer
+pub(super) type Entry = (u64, Arc<TimerShared>);
+
+pub(super) struct Wheel {
+    /// the tick the wheel has been advanced to
+    elapsed: u64,
+    levels: Box<[Level]>,
+}
+
+struct Level {
+    level: usize,
+    /// a bit for every slot that has entries
+    occupied: u64,
+    slots: [Vec<Entry>; LEVEL_MULT],
+}
+
+/// the next slot that needs to be processed
+struct Expiration {
+    level: usize,
+    slot: usize,
+    deadline: u64,
+}
+
+impl Wheel {
+    pub(super) fn new() -> Self {
+        Self {
+            elapsed: 0,
+            levels: (0..NUM_LEVELS)
+                .map(|level| Level {
+                    level,
+                    occupied: 0,
+                    slots: core::array::from_fn(|_| Vec::new()),
+                })
+                .collect(),
+        }
+    }
+
+    /// adds a timer that expires at tick `when`.
+    /// hands it back if `when` has already passed
+    pub(super) fn insert(
+        &mut self,
+        when: u64,
+        timer: Arc<TimerShared>,
+    ) -> Result<(), Arc<TimerShared>> {
+        if when <= self.elapsed {
+            return Err(timer);
+        }
+
+        let level = level_for(self.elapsed, when);
+        self.levels[level].add((when, timer));
+        Ok(())
+    }
+
+    /// takes out the entry `timer` was inserted with for tick `when`, if it's still in the wheel
+    pub(super) fn remove(&mut self, when: u64, timer: &Arc<TimerShared>) {
+        let (level, index) = timer.position();
+        if let Some(level) = self.levels.get_mut(level) {
+            level.remove(when, index, timer);
+        }
+    }
+
+    /// how many entries there are, expired or not
+    #[cfg(test)]
+    pub(super) fn len(&self) -> usize {
+        self.levels
+            .iter()
+            .flat_map(|level| &level.slots)
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// the tick the next slot with timers in it comes up at
+    pub(super) fn next_deadline(&self) -> Option<u64> {
+        self.next_expiration().map(|expiration| expiration.deadline)
+    }
+
+    /// advances the wheel to tick `now`, pushing every timer that expired to `expired`
+    pub(super) fn poll(&mut self, now: u64, expired: &mut Vec<Entry>) {
+        while let Some(expiration) = self.next_expiration()
+            && expiration.deadline <= now
+        {
+            let entries = self.levels[expiration.level].take(expiration.slot);
+            self.elapsed = expiration.deadline;
+
+            for (when, timer) in entries {
+                if !timer.is_registered_for(when) {
+                    continue;
+                }
+                // timers that aren't due yet move down a level
+                if let Err(timer) = self.insert(when, timer) {
+                    expired.push((when, timer));
+                }
+            }
+        }
+
+        self.elapsed = self.elapsed.max(now);
+    }
+
+    fn next_expiration(&self) -> Option<Expiration> {
+        // timers on lower levels always expire before the ones on higher levels
+        self.levels
+            .iter()
+            .find_map(|level| level.next_expiration(self.elapsed))
+    }
+}
+
+impl Level {
+    fn add(&mut self, (when, timer): Entry) {
+        let slot = slot_for(when, self.level);
+        timer.set_position(self.level, self.slots[slot].len());
+        self.slots[slot].push((when, timer));
+        self.occupied |= 1 << slot;
+    }
+
+    /// takes out the entry of `timer` at `index` of the slot of `when`.
+    /// the position of a timer goes stale once its slot is taken, so this checks it's really there
+    fn remove(&mut self, when: u64, index: usize, timer: &Arc<TimerShared>) {
+        let slot = slot_for(when, self.level);
+        let entries = &mut self.slots[slot];
+        match entries.get(index) {
+            Some((_, entry)) if Arc::ptr_eq(entry, timer) => {}
+            _ => return,
+        }
+
+        entries.swap_remove(index);
+        if let Some((_, moved)) = entries.get(index) {
+            moved.set_position(self.level, index);
+        }
+        if entries.is_empty() {
+            self.occupied &= !(1 << slot);
+        }
+    }
+
+    fn take(&mut self, slot: usize) -> Vec<Entry> {
+        self.occupied &= !(1 << slot);
+        core::mem::take(&mut self.slots[slot])
+    }
+
+    fn next_expiration(&self, now: u64) -> Option<Expiration> {
+        if self.occupied == 0 {
+            return None;
+        }
+
+        let slot_range = slot_range(self.level);
+        let level_range = slot_range * LEVEL_MULT as u64;
+
+        // the first occupied slot at or after the one `now` is in
+        let now_slot = (now / slot_range) as u32;
+        let slot = (self.occupied.rotate_right(now_slot).trailing_zeros() + now_slot) as usize
+            % LEVEL_MULT;
+
+        let level_start = now & !(level_range - 1);
+        let mut deadline = level_start + slot as u64 * slot_range;
+        if deadline <= now {
+            // only the top level wraps around, see `MAX_DURATION`
+            debug_assert_eq!(self.level, NUM_LEVELS - 1);
+            deadline += level_range;
+        }
+
+        Some(Expiration {
+            level: self.level,
+            slot,
+            deadline,
+        })
+    }
+}
+
+fn slot_range(level: usize) -> u64 {
+    (LEVEL_MULT as u64).pow(level as u32)
+}
+
+fn slot_for(when: u64, level: usize) -> usize {
+    ((when >> (level * 6)) & SLOT_MASK) as usize
+}
+
+/// the level a timer at `when` goes into, which is the highest level on which it differs from `elapsed`
+fn level_for(elapsed: u64, when: u64) -> usize {
+    let masked = ((elapsed ^ when) | SLOT_MASK).min(MAX_DURATION);
+    let significant = 63 - masked.leading_zeros() as usize;
+    significant / 6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(wheel: &mut Wheel, when: u64) -> Arc<TimerShared> {
+        let timer = Arc::new(TimerShared::new());
+        timer.set_registered(when);
+        assert!(wheel.insert(when, timer.clone()).is_ok());
+        timer
+    }
+
+    fn poll(wheel: &mut Wheel, now: u64) -> usize {
+        let mut expired = Vec::new();
+        wheel.poll(now, &mut expired);
+        expired.len()
+    }
+
+    #[test]
+    fn timers_expire_in_order() {
+        let mut wheel = Wheel::new();
+        for when in [1, 63, 64, 100, 5000, 300_000] {
+            timer(&mut wheel, when);
+        }
+
+        assert_eq!(wheel.next_deadline(), Some(1));
+        assert_eq!(poll(&mut wheel, 1), 1);
+        assert_eq!(poll(&mut wheel, 62), 0);
+        assert_eq!(poll(&mut wheel, 64), 2);
+        assert_eq!(poll(&mut wheel, 4999), 1);
+        assert_eq!(poll(&mut wheel, 5000), 1);
+        assert_eq!(poll(&mut wheel, 299_999), 0);
+        assert_eq!(poll(&mut wheel, 1_000_000), 1);
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn stale_entries_are_skipped() {
+        let mut wheel = Wheel::new();
+        let reset = timer(&mut wheel, 10);
+        reset.set_registered(20);
+        assert!(wheel.insert(20, reset.clone()).is_ok());
+        let dropped = timer(&mut wheel, 15);
+        dropped.set_unregistered();
+
+        assert_eq!(poll(&mut wheel, 15), 0);
+        assert_eq!(poll(&mut wheel, 20), 1);
+    }
+
+    #[test]
+    fn removed_entries_are_gone() {
+        let mut wheel = Wheel::new();
+        let timers: Vec<_> = [5, 5, 5, 100, 100, 300_000]
+            .into_iter()
+            .map(|when| (when, timer(&mut wheel, when)))
+            .collect();
+
+        // from the middle of a slot first, which moves the last entry of the slot into its place
+        for index in [1, 0, 2, 5, 3, 4] {
+            let (when, timer) = &timers[index];
+            wheel.remove(*when, timer);
+        }
+        assert_eq!(wheel.len(), 0);
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn far_timers_wrap_around_the_top_level() {
+        let mut wheel = Wheel::new();
+        timer(&mut wheel, MAX_DURATION * 3);
+
+        assert_eq!(poll(&mut wheel, MAX_DURATION * 2), 0);
+        assert_eq!(poll(&mut wheel, MAX_DURATION * 3), 1);
+    }
+
+    #[test]
+    fn past_deadlines_are_rejected() {
+        let mut wheel = Wheel::new();
+        assert_eq!(poll(&mut wheel, 10), 0);
+        assert!(wheel.insert(10, Arc::new(TimerShared::new())).is_err());
+    }
+}
@@ -1,3 +1,49 @@
-use alloc::sync::Arc;
-use core::{marker::PhantomData, sync::atomic::AtomicUsize};
-//use yage_task::runnable::Handle;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use yage_task::handle::{JoinError, JoinHandle};
+
+/// the internal handle to a task spawned on an `Executor`.
+/// `'a` is the lifetime of anything the task borrows
+pub struct TaskHandle<'a, T, M> {
+    inner: JoinHandle<T, M>,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a, T, M> TaskHandle<'a, T, M> {
+    pub(crate) fn new(inner: JoinHandle<T, M>) -> Self {
+        Self {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+
+    /// blocks until the task completes, returning `None` if it was closed before it could
+    pub fn join(self) -> Option<T> {
+        self.inner.join()
+    }
+
+    /// blocks until the task completes, returning a panic as an error instead of resuming it
+    pub fn try_join(self) -> Result<T, JoinError> {
+        self.inner.try_join()
+    }
+
+    /// cancels the task, returning the output if it had already completed
+    pub fn cancel(self) -> Option<T> {
+        self.inner.cancel()
+    }
+
+    /// lets the task run to completion in the background
+    pub fn detach(self) {
+        self.inner.detach()
+    }
+}
+
+impl<'a, T, M> Future for TaskHandle<'a, T, M> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
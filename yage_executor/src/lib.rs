@@ -7,20 +7,43 @@ extern crate alloc;
 extern crate std as alloc;
 
 use alloc::sync::Arc;
-use concurrent_queue::ConcurrentQueue;
+use concurrent_queue::{ConcurrentQueue, PushError};
 use core::{
+    cell::Cell,
+    future::Future,
     marker::PhantomData,
-    sync::atomic::{AtomicPtr, AtomicUsize},
-    task::Waker,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
 };
-use simeng_task::{builder::Builder, runnable::Task};
+use park::{BlockOn, Parker};
 use slab::Slab;
+#[cfg(feature = "registry")]
+use yage_task::registry::{Registry, TaskSnapshot};
+use yage_task::{builder::Builder, task::Task};
+use yage_util::atomic::Atomic;
 
 /// TODO: when stablized, change this back to private
 pub mod handle;
 
+pub use yage_task::coop;
+
+#[cfg(feature = "std")]
+mod blocking;
+#[cfg(feature = "std")]
+mod context;
 #[cfg(feature = "std")]
 mod driver;
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod net;
+mod park;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "std")]
+pub mod signal;
+#[cfg(feature = "std")]
+pub mod time;
 
 mod sealed {
     pub trait Sealed {}
@@ -47,14 +70,179 @@ impl ExecutorMarker for NotThreadSafe {
 
 struct ExecutorInner {
     task_queue: ConcurrentQueue<Task<usize>>,
-    wakers: Arc<AtomicPtr<Slab<Waker>>>,
+    /// the wakers of every task that hasn't completed yet, so they can be cancelled when the executor shuts down
+    wakers: Atomic<Slab<Waker>>,
+    /// set while `Executor::shutdown` waits for the tasks to complete
+    draining: AtomicBool,
+    /// the tasks that are woken while `Executor::shutdown` waits for them, once `task_queue` has been closed
+    /// so nothing new can be spawned. only the thread in `Executor::shutdown` runs these
+    drain_queue: ConcurrentQueue<Task<usize>>,
+    metrics: metrics::TaskMetrics,
+    /// parks the thread in `Executor::shutdown`, and is where the parkers of the other threads come from
+    parker: Arc<Parker>,
+    /// the parkers of the threads in `Executor::block_on`, each of which has its own,
+    /// since any of them can run the tasks that are scheduled
+    blocked: Atomic<Slab<Arc<Parker>>>,
+    #[cfg(feature = "std")]
+    pool: pool::Pool,
+    #[cfg(feature = "std")]
+    driver: driver::DriverCell,
+    #[cfg(feature = "std")]
+    blocking: blocking::BlockingPool,
+}
+
+impl ExecutorInner {
+    fn new(
+        #[cfg_attr(not(feature = "std"), allow(unused_variables))] workers: usize,
+        #[cfg(feature = "std")] clock: time::Clock,
+    ) -> Self {
+        #[cfg(feature = "std")]
+        let driver = driver::DriverCell::new(clock);
+        Self {
+            task_queue: ConcurrentQueue::unbounded(),
+            wakers: Atomic::new(Slab::new()),
+            draining: AtomicBool::new(false),
+            drain_queue: ConcurrentQueue::unbounded(),
+            metrics: metrics::TaskMetrics::new(),
+            blocked: Atomic::new(Slab::new()),
+            #[cfg(feature = "std")]
+            parker: Arc::new(Parker::new(driver.clone())),
+            #[cfg(not(feature = "std"))]
+            parker: Arc::new(Parker::new()),
+            #[cfg(feature = "std")]
+            pool: pool::Pool::new(workers, &driver),
+            #[cfg(feature = "std")]
+            blocking: blocking::BlockingPool::new(&driver),
+            #[cfg(feature = "std")]
+            driver,
+        }
+    }
+
+    /// queues `task` to be run, waking up the executor if it's asleep.
+    /// tasks woken on a worker thread stay on that worker.
+    /// hands the task back if the queue has been closed, unless the executor is draining
+    fn schedule(&self, task: Task<usize>) -> Result<(), Task<usize>> {
+        #[cfg(feature = "std")]
+        let task = match self.pool.push_local(task) {
+            Ok(()) => return Ok(()),
+            Err(task) => task,
+        };
+
+        match self.task_queue.push(task) {
+            Ok(()) => {
+                self.parker.unpark();
+                for (_, parker) in self.blocked.borrow().iter() {
+                    parker.unpark();
+                }
+                #[cfg(feature = "std")]
+                self.pool.notify_one();
+                Ok(())
+            }
+            Err(PushError::Closed(task)) if self.draining.load(Ordering::SeqCst) => {
+                // it's never closed
+                let _ = self.drain_queue.push(task);
+                self.parker.unpark();
+                Ok(())
+            }
+            Err(PushError::Closed(task) | PushError::Full(task)) => Err(task),
+        }
+    }
+
+    /// takes the task `key` is for out of the live tasks, once its future is gone
+    fn untrack(&self, key: usize) {
+        let mut wakers = self.wakers.borrow_mut();
+        // everything is taken out at once when the executor shuts down
+        let waker = wakers.try_remove(key);
+        let empty = wakers.is_empty();
+        drop(wakers);
+        drop(waker);
+
+        if empty && self.draining.load(Ordering::SeqCst) {
+            self.parker.unpark();
+        }
+    }
+
+    /// cancels every task that hasn't completed yet.
+    ///
+    /// this wakes them up, and since `task_queue` has to be closed by now, they are dropped instead of queued up.
+    /// the ones that are already queued up are left for whoever closed the queue to drop
+    fn cancel_all(&self) {
+        let wakers: alloc::vec::Vec<_> = self.wakers.borrow_mut().drain().collect();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// the future of a task spawned on an executor, which is kept track of until it's dropped
+struct Tracked<F> {
+    future: F,
+    inner: Arc<ExecutorInner>,
+    key: usize,
+    polls: u64,
+    completed: bool,
+}
+
+impl<F: Future> Future for Tracked<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `future` is never moved out of while pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        #[cfg(feature = "std")]
+        let start = std::time::Instant::now();
+        let poll = future.poll(cx);
+        #[cfg(feature = "std")]
+        this.inner.metrics.polled(start.elapsed());
+        #[cfg(not(feature = "std"))]
+        this.inner.metrics.polled(core::time::Duration::ZERO);
+
+        this.polls += 1;
+        this.completed = poll.is_ready();
+        poll
+    }
+}
+
+impl<F> Drop for Tracked<F> {
+    fn drop(&mut self) {
+        self.inner.metrics.dropped(self.polls, self.completed);
+        self.inner.untrack(self.key);
+    }
+}
+
+/// the parker of a thread in `Executor::block_on`, which is woken whenever a task is scheduled until this is dropped
+struct Blocked<'a> {
+    inner: &'a ExecutorInner,
+    key: usize,
+}
+
+impl<'a> Blocked<'a> {
+    fn new(inner: &'a ExecutorInner, parker: Arc<Parker>) -> Self {
+        let key = inner.blocked.borrow_mut().insert(parker);
+        Self { inner, key }
+    }
+}
+
+impl Drop for Blocked<'_> {
+    fn drop(&mut self) {
+        self.inner.blocked.borrow_mut().remove(self.key);
+    }
 }
 
 pub struct Executor<M: ExecutorMarker> {
-    inner: ExecutorInner,
+    inner: Arc<ExecutorInner>,
     task_id: AtomicUsize,
+    /// the state of the generator picking the next task to run, if they are run in a seeded order.
+    /// only ever set on an `Executor<NotThreadSafe>`
+    seed: Option<Cell<u64>>,
+    #[cfg(feature = "registry")]
+    registry: Registry<usize>,
     #[cfg(feature = "std")]
     reactor_handle: Option<std::thread::JoinHandle<()>>,
+    #[cfg(feature = "std")]
+    workers: alloc::vec::Vec<std::thread::JoinHandle<()>>,
     _marker: PhantomData<M>,
 }
 
@@ -62,33 +250,509 @@ unsafe impl<M: ExecutorMarker> Send for Executor<M> where M::Marker: Send + Sync
 unsafe impl<M: ExecutorMarker> Sync for Executor<M> where M::Marker: Send + Sync {}
 
 impl Executor<ThreadSafe> {
+    /// creates an executor with a worker thread for every core.
+    ///
+    /// without `std` there are no worker threads,
+    /// so tasks only run through `Executor::tick`, `Executor::run_until_stalled` and `Executor::block_on`
     pub fn new_sync() -> Self {
-        Self {
-            inner: ExecutorInner {
-                task_queue: ConcurrentQueue::unbounded(),
-                wakers: Arc::new(AtomicPtr::new(core::ptr::null_mut())),
-            },
-            task_id: AtomicUsize::new(1),
-            #[cfg(feature = "std")]
-            reactor_handle: None,
-            _marker: PhantomData,
+        #[cfg(feature = "std")]
+        {
+            let workers = std::thread::available_parallelism().map_or(1, core::num::NonZero::get);
+            Self::with_workers(workers)
         }
+        #[cfg(not(feature = "std"))]
+        Self::from_inner(ExecutorInner::new(0), 1)
+    }
+
+    /// creates an executor with `workers` worker threads.
+    ///
+    /// tasks still run on the calling thread through `Executor::tick` and friends,
+    /// so with no workers, they only run there
+    #[cfg(feature = "std")]
+    pub fn with_workers(workers: usize) -> Self {
+        let mut executor = Self::from_inner(ExecutorInner::new(workers, time::Clock::System), 1);
+        executor.workers = (0..workers)
+            .map(|index| {
+                let inner = executor.inner.clone();
+                std::thread::Builder::new()
+                    .name(alloc::format!("yage-worker-{index}"))
+                    .spawn(move || {
+                        let _enter = context::enter(&inner);
+                        inner.pool.run(index, &inner.task_queue)
+                    })
+                    .expect("failed to spawn a worker thread")
+            })
+            .collect();
+        executor
+    }
+}
+
+impl Executor<ThreadSafe> {
+    /// spawns a task onto this executor.
+    /// it is run by the worker threads, or by `Executor::tick` and friends if there are none
+    pub fn spawn<F>(&self, future: F) -> TaskHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let future = self.track(future);
+        let key = future.key;
+        let inner = self.inner.clone();
+        let (task, handle) = self.builder().spawn(
+            |_| future,
+            move |task| {
+                // the executor is gone, so the task is dropped along with its future
+                let _ = inner.schedule(task);
+            },
+        );
+        self.start(task, key);
+
+        TaskHandle(handle::TaskHandle::new(handle))
     }
 }
 
 impl Executor<NotThreadSafe> {
     pub fn new_unsync() -> Self {
+        #[cfg(feature = "std")]
+        {
+            Self::with_clock(time::Clock::System)
+        }
+        #[cfg(not(feature = "std"))]
+        Self::from_inner(ExecutorInner::new(0), 0)
+    }
+
+    /// creates an executor whose timers go by `clock`.
+    /// with a `MockClock`, they only fire when it's advanced
+    #[cfg(feature = "std")]
+    pub fn with_clock(clock: impl Into<time::Clock>) -> Self {
+        Self::from_inner(ExecutorInner::new(0, clock.into()), 0)
+    }
+
+    /// runs tasks in an order picked by a generator seeded with `seed`, instead of the order they were woken in.
+    ///
+    /// the same seed always gives the same order, as long as the tasks are woken in the same order,
+    /// which shakes out tasks depending on running in a certain order in a reproducible way
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(Cell::new(seed));
+        self
+    }
+}
+
+impl Executor<NotThreadSafe> {
+    /// spawns a task onto this executor.
+    /// unlike `Executor<ThreadSafe>`, the future doesn't have to be `Send`
+    pub fn spawn<F>(&self, future: F) -> TaskHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let future = self.track(future);
+        let key = future.key;
+        let inner = self.inner.clone();
+        #[cfg(feature = "std")]
+        let owner = std::thread::current().id();
+        let schedule = move |task| {
+            // the executor is gone. dropping the task would drop the future on whatever thread
+            // woke it up, so it's leaked instead, unless that is the thread that owned the executor
+            if let Err(task) = inner.schedule(task) {
+                #[cfg(feature = "std")]
+                if std::thread::current().id() == owner {
+                    drop(task);
+                    return;
+                }
+                core::mem::forget(task);
+            }
+        };
+
+        // SAFETY: the future is only ever polled or dropped through a `Task`, and a `Task` only ever
+        // leaves the queue on the thread that owns this executor (it isn't `Send`), or is dropped by
+        // the schedule function on that thread.
+        // wakers can still be sent to other threads, but all they do with the future is queue it up.
+        // `F` is `'static`, so nothing it borrows can go away before it does
+        let (task, handle) = unsafe { self.builder().spawn_unchecked(|_| future, schedule) };
+        self.start(task, key);
+
+        TaskHandle(handle::TaskHandle::new(handle))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Executor<NotThreadSafe> {
+    /// runs the tasks that are ready when this is called, until `deadline` has passed on the clock of this executor.
+    ///
+    /// tasks woken while this runs, like one that wakes itself, are left for the next call,
+    /// so this returns even if the clock never moves, as with a `MockClock`.
+    /// a task that is already running when the deadline passes isn't interrupted,
+    /// so this can overshoot by as long as a single poll takes.
+    /// returns how many tasks are still ready to run
+    pub fn run_for(&self, deadline: std::time::Instant) -> usize {
+        if let Some(driver) = self.inner.driver.get() {
+            driver.poll();
+        }
+
+        let clock = self.inner.driver.clock();
+        let ready = self.inner.task_queue.len();
+        for _ in 0..ready {
+            if clock.now() >= deadline || !self.tick() {
+                break;
+            }
+        }
+        self.inner.task_queue.len()
+    }
+
+    /// runs ready tasks for at most `budget`, returning how many are still ready to run.
+    /// this is meant to be called once per frame, see `Executor::run_for`
+    pub fn tick_frame(&self, budget: core::time::Duration) -> usize {
+        self.run_for(self.inner.driver.clock().now() + budget)
+    }
+}
+
+impl<M: ExecutorMarker> Executor<M> {
+    fn from_inner(inner: ExecutorInner, first_id: usize) -> Self {
         Self {
-            inner: ExecutorInner {
-                task_queue: ConcurrentQueue::unbounded(),
-                wakers: Arc::new(AtomicPtr::new(core::ptr::null_mut())),
-            },
-            task_id: AtomicUsize::new(0),
+            inner: Arc::new(inner),
+            task_id: AtomicUsize::new(first_id),
+            seed: None,
+            #[cfg(feature = "registry")]
+            registry: Registry::new(),
             #[cfg(feature = "std")]
             reactor_handle: None,
+            #[cfg(feature = "std")]
+            workers: alloc::vec::Vec::new(),
             _marker: PhantomData,
         }
     }
+
+    /// runs a single scheduled task.
+    /// returns `false` if there was nothing to run.
+    ///
+    /// with I/O enabled, this checks for I/O events without waiting when nothing is scheduled
+    pub fn tick(&self) -> bool {
+        #[cfg(feature = "std")]
+        let _enter = context::enter(&self.inner);
+
+        let task = self.pop().or_else(|| self.inner.drain_queue.pop().ok());
+        #[cfg(feature = "std")]
+        let task = task.or_else(|| {
+            let driver = self.inner.driver.get()?;
+            driver.poll().then(|| self.pop())?
+        });
+
+        match task {
+            Some(task) => {
+                task.run();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// takes the next task to run out of the queue
+    fn pop(&self) -> Option<Task<usize>> {
+        let Some(seed) = &self.seed else {
+            return self.inner.task_queue.pop().ok();
+        };
+
+        // only the thread owning the executor takes tasks out of the queue,
+        // so moving the ones in front of the picked task to the back doesn't race anything
+        let len = self.inner.task_queue.len();
+        if len == 0 {
+            return None;
+        }
+        if self.inner.task_queue.is_closed() {
+            // nothing could be put back
+            return self.inner.task_queue.pop().ok();
+        }
+        let skip = splitmix64(seed) % len as u64;
+        for _ in 0..skip {
+            let task = self.inner.task_queue.pop().ok()?;
+            // the queue is only closed by `Executor::shutdown` and when the executor is dropped,
+            // neither of which can happen while this runs
+            let _ = self.inner.task_queue.push(task);
+        }
+        self.inner.task_queue.pop().ok()
+    }
+
+    /// runs scheduled tasks until there are none left, returning how many were run.
+    /// tasks that are woken while this is running are run as well
+    pub fn run_until_stalled(&self) -> usize {
+        let mut ran = 0;
+        while self.tick() {
+            ran += 1;
+        }
+        ran
+    }
+
+    /// runs `future` to completion on the current thread, running tasks in between polls.
+    ///
+    /// when there is nothing to do, the thread is parked until either `future` or a task is woken.
+    /// without `std`, this spins instead
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "std")]
+        let _enter = context::enter(&self.inner);
+
+        let mut future = core::pin::pin!(future);
+
+        // every call parks on its own, so concurrent calls can't take each other's wakeups
+        let parker = Arc::new(self.inner.parker.sibling());
+        let _blocked = Blocked::new(&self.inner, parker.clone());
+        let block_on = Arc::new(BlockOn {
+            woken: AtomicBool::new(true),
+            parker: parker.clone(),
+        });
+        let waker = Waker::from(block_on.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if block_on.woken.swap(false, Ordering::Acquire)
+                && let Poll::Ready(out) = future.as_mut().poll(&mut cx)
+            {
+                return out;
+            }
+
+            if !self.tick() && !block_on.woken.load(Ordering::Acquire) {
+                parker.park();
+            }
+        }
+    }
+
+    /// runs `f` on a thread of the blocking pool, which is meant for work that blocks the thread,
+    /// like reading files or decoding images, so it doesn't hold up other tasks.
+    ///
+    /// the pool spawns a thread whenever all of its threads are busy, up to a maximum,
+    /// see `Executor::with_blocking_pool`. if they are all busy then, `f` waits for one of them.
+    /// dropping the executor waits for the closures that are already running, and drops the others
+    #[cfg(feature = "std")]
+    pub fn spawn_blocking<F, T>(&self, f: F) -> TaskHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let future = self.track(blocking::BlockingTask(Some(f)));
+        let key = future.key;
+        let inner = self.inner.clone();
+        let (task, handle) = self.builder().spawn(
+            |_| future,
+            move |task| {
+                // the executor is gone, so the task is dropped along with its closure
+                let _ = inner.blocking.schedule(task);
+            },
+        );
+        self.start(task, key);
+
+        TaskHandle(handle::TaskHandle::new(handle))
+    }
+
+    /// lets the blocking pool spawn up to `max_threads`, which exit after `idle_timeout` without anything to run.
+    /// it's 512 threads and 10 seconds by default.
+    ///
+    /// # Panics
+    /// if `max_threads` is zero
+    #[cfg(feature = "std")]
+    pub fn with_blocking_pool(
+        self,
+        max_threads: usize,
+        idle_timeout: core::time::Duration,
+    ) -> Self {
+        assert!(
+            max_threads > 0,
+            "the blocking pool needs at least one thread"
+        );
+        self.inner.blocking.configure(max_threads, idle_timeout);
+        self
+    }
+
+    /// shuts the executor down, giving the tasks that are still alive up to `timeout` to complete.
+    ///
+    /// nothing new can be spawned from here on: tasks spawned by the ones that are still alive are dropped right away,
+    /// so their handles return `None`.
+    /// the tasks there already are keep running on the workers as well as this thread, which drives I/O and timers too,
+    /// and so do the closures passed to `Executor::spawn_blocking`.
+    /// after that, the tasks that are left are cancelled, the same as dropping the executor does right away:
+    /// the reactor thread is stopped, and every I/O source is released, so anything still waiting on one gets an error.
+    /// closures that are still running are left to finish in the background
+    #[cfg(feature = "std")]
+    pub fn shutdown(self, timeout: core::time::Duration) {
+        let deadline = std::time::Instant::now().checked_add(timeout);
+        self.drain(deadline);
+        self.inner.blocking.shutdown(deadline);
+    }
+
+    /// stops new tasks from being spawned, and runs the ones there are until they complete or `deadline` passes
+    #[cfg(feature = "std")]
+    fn drain(&self, deadline: Option<std::time::Instant>) {
+        let _enter = context::enter(&self.inner);
+        // from here on, the last task to complete wakes us up,
+        // and the tasks that are woken up go to `drain_queue` instead
+        self.inner.draining.store(true, Ordering::SeqCst);
+        self.inner.task_queue.close();
+
+        while !self.inner.wakers.borrow().is_empty() {
+            if self.tick() {
+                continue;
+            }
+            match deadline {
+                Some(deadline) => {
+                    let now = std::time::Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    self.inner.parker.park_timeout(deadline - now);
+                }
+                None => self.inner.parker.park(),
+            }
+        }
+        // whatever is woken up from here on is dropped
+        self.inner.draining.store(false, Ordering::SeqCst);
+    }
+
+    /// enables the I/O driver.
+    ///
+    /// it is driven by the thread in `Executor::block_on` or an idle worker thread when there are no tasks to run,
+    /// and checked by `Executor::tick` when nothing is scheduled.
+    /// use `Executor::spawn_reactor` to always have a thread waiting on it
+    #[cfg(feature = "std")]
+    pub fn enable_io(&self) -> std::io::Result<()> {
+        self.io_driver().map(drop)
+    }
+
+    /// drives I/O on a dedicated reactor thread, enabling it if it hasn't been yet
+    #[cfg(feature = "std")]
+    pub fn spawn_reactor(&mut self) -> std::io::Result<()> {
+        if self.reactor_handle.is_some() {
+            return Ok(());
+        }
+
+        let io = self.io_driver()?;
+        self.reactor_handle = Some(
+            std::thread::Builder::new()
+                .name("yage-reactor".into())
+                .spawn(move || io.run())?,
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn io_driver(&self) -> std::io::Result<Arc<driver::IoDriver>> {
+        self.inner.driver.get_or_init().cloned()
+    }
+
+    /// wraps `future` so the executor keeps track of its task until it completes or is cancelled.
+    /// the task has to be started with `Executor::start`
+    fn track<F>(&self, future: F) -> Tracked<F> {
+        let key = self.inner.wakers.borrow_mut().insert(Waker::noop().clone());
+        self.inner.metrics.spawned();
+        Tracked {
+            future,
+            inner: self.inner.clone(),
+            key,
+            polls: 0,
+            completed: false,
+        }
+    }
+
+    /// schedules a task that was just spawned with a future from `Executor::track`.
+    /// once the executor is shutting down, the task is dropped instead
+    fn start(&self, task: Task<usize>, key: usize) {
+        if self.inner.task_queue.is_closed() {
+            return drop(task);
+        }
+        if let Some(waker) = self.inner.wakers.borrow_mut().get_mut(key) {
+            *waker = task.waker();
+        }
+        task.schedule();
+    }
+
+    /// a builder for the next task spawned on this executor, with its id as metadata
+    pub(crate) fn builder(&self) -> Builder<usize> {
+        let id = self
+            .task_id
+            .fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        let builder = Builder::new().metadata(id);
+        #[cfg(feature = "registry")]
+        let builder = builder.registry(&self.registry);
+        builder
+    }
+
+    /// a snapshot of the metrics of this executor.
+    ///
+    /// without `std`, nothing is timed, and there are no workers or I/O driver to keep metrics for
+    pub fn metrics(&self) -> metrics::Metrics {
+        let mut metrics = metrics::Metrics::default();
+        self.inner.metrics.snapshot(&mut metrics);
+        metrics.queue_depth = self.inner.task_queue.len();
+
+        #[cfg(feature = "std")]
+        {
+            metrics.queue_depth += self.inner.pool.queue_depth();
+            metrics.steals = self.inner.pool.steals();
+            metrics.time_parked = self.inner.parker.time_parked() + self.inner.pool.time_parked();
+            if let Some(driver) = self.inner.driver.get() {
+                metrics.events_per_tick = driver.events_per_tick();
+            }
+        }
+        metrics
+    }
+
+    /// a snapshot of every live task spawned on this executor, ordered by when they were spawned.
+    ///
+    /// the metadata of each task is its id
+    #[cfg(feature = "registry")]
+    pub fn dump_tasks(&self) -> alloc::vec::Vec<TaskSnapshot<usize>> {
+        self.registry.tasks()
+    }
+}
+
+impl<M: ExecutorMarker> Drop for Executor<M> {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            self.inner.pool.shutdown();
+            for worker in self.workers.drain(..) {
+                // the executor can be dropped by one of its own tasks
+                if worker.thread().id() != std::thread::current().id() {
+                    let _ = worker.join();
+                }
+            }
+            self.inner.blocking.shutdown(None);
+        }
+
+        // nothing new can be queued up from here on, so the tasks that are woken up are dropped instead
+        self.inner.task_queue.close();
+        self.inner.cancel_all();
+
+        #[cfg(feature = "std")]
+        {
+            if let Some(driver) = self.inner.driver.get() {
+                driver.shutdown();
+            }
+            if let Some(reactor) = self.reactor_handle.take() {
+                let _ = reactor.join();
+            }
+        }
+
+        // and whatever was already queued up is dropped here
+        loop {
+            let task = self.inner.task_queue.pop().ok();
+            let task = task.or_else(|| self.inner.drain_queue.pop().ok());
+            #[cfg(feature = "std")]
+            let task = task.or_else(|| self.inner.pool.pop_any());
+            match task {
+                Some(task) => drop(task),
+                None => break,
+            }
+        }
+    }
+}
+
+/// advances `state` and returns the next number of the sequence
+fn splitmix64(state: &Cell<u64>) -> u64 {
+    let next = state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+    state.set(next);
+    let mut z = next;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
 }
 
 pub struct TaskHandle<T>(handle::TaskHandle<'static, T, usize>);
@@ -97,4 +761,375 @@ impl<T> TaskHandle<T> {
     pub fn join(self) -> Option<T> {
         self.0.join()
     }
+
+    /// blocks until the task completes.
+    /// if the task panicked, the panic is returned instead of being resumed
+    pub fn try_join(self) -> Result<T, yage_task::handle::JoinError> {
+        self.0.try_join()
+    }
+
+    /// cancels the task, dropping its future the next time it is scheduled.
+    /// returns the output if the task had already completed
+    pub fn cancel(self) -> Option<T> {
+        self.0.cancel()
+    }
+
+    /// lets the task keep running without a handle.
+    /// dropping a `TaskHandle` does the same thing
+    pub fn detach(self) {
+        self.0.detach()
+    }
+}
+
+impl<T> Future for TaskHandle<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    use std::rc::Rc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// pending until `ready` is set
+    struct WaitFor {
+        ready: Arc<AtomicBool>,
+        waker: Arc<Mutex<Option<Waker>>>,
+    }
+
+    impl Future for WaitFor {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            *self.waker.lock().unwrap() = Some(cx.waker().clone());
+            if self.ready.load(Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn spawn_and_tick() {
+        let executor = Executor::with_workers(0);
+        let handle = executor.spawn(async { 1 + 2 });
+
+        assert!(executor.tick());
+        assert!(!executor.tick());
+        assert_eq!(handle.join(), Some(3));
+    }
+
+    #[test]
+    fn run_until_stalled_runs_woken_tasks() {
+        let executor = Executor::new_unsync();
+        let counter = Rc::new(core::cell::Cell::new(0));
+
+        let inner = counter.clone();
+        let first = executor.spawn(async move {
+            inner.set(inner.get() + 1);
+        });
+        let inner = counter.clone();
+        let second = executor.spawn(async move {
+            first.await;
+            inner.set(inner.get() + 1);
+        });
+
+        assert_eq!(executor.run_until_stalled(), 2);
+        assert_eq!(counter.get(), 2);
+        assert!(second.join().is_some());
+    }
+
+    #[test]
+    fn block_on_awaits_tasks() {
+        let executor = Executor::new_unsync();
+        let local = Rc::new(5);
+
+        let value = local.clone();
+        let handle = executor.spawn(async move { *value * 2 });
+        assert_eq!(executor.block_on(handle), 10);
+        assert_eq!(executor.block_on(async { 3 }), 3);
+    }
+
+    #[test]
+    fn block_on_parks_until_woken() {
+        let executor = Executor::new_sync();
+        let ready = Arc::new(AtomicBool::new(false));
+        let waker = Arc::new(Mutex::new(None));
+
+        let handle = executor.spawn(WaitFor {
+            ready: ready.clone(),
+            waker: waker.clone(),
+        });
+
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            ready.store(true, Ordering::Release);
+            waker.lock().unwrap().take().unwrap().wake();
+        });
+
+        executor.block_on(handle);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn concurrent_block_on_calls_are_woken_separately() {
+        let executor = Executor::with_workers(0);
+
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        let ready = Arc::new(AtomicBool::new(false));
+                        let waker = Arc::new(Mutex::new(None));
+                        let future = WaitFor {
+                            ready: ready.clone(),
+                            waker: waker.clone(),
+                        };
+
+                        let waking = std::thread::spawn(move || {
+                            while waker.lock().unwrap().is_none() {
+                                std::thread::yield_now();
+                            }
+                            ready.store(true, Ordering::Release);
+                            waker.lock().unwrap().take().unwrap().wake();
+                        });
+                        // a task in between, which either thread can end up running
+                        let task = executor.spawn(async {});
+                        executor.block_on(async {
+                            task.await;
+                            future.await;
+                        });
+                        waking.join().unwrap();
+                    }
+                });
+            }
+        });
+    }
+
+    /// records which thread it ran on, after hogging it for a bit
+    fn busy(threads: Arc<Mutex<std::collections::HashSet<std::thread::ThreadId>>>) {
+        std::thread::sleep(Duration::from_millis(5));
+        threads.lock().unwrap().insert(std::thread::current().id());
+    }
+
+    #[test]
+    fn tasks_spread_across_workers() {
+        let executor = Executor::with_workers(4);
+        let threads = Arc::new(Mutex::new(std::collections::HashSet::new()));
+
+        let handles: std::vec::Vec<_> = (0..16)
+            .map(|_| {
+                let threads = threads.clone();
+                executor.spawn(async move { busy(threads) })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(threads.lock().unwrap().len() > 1);
+        assert!(
+            !threads
+                .lock()
+                .unwrap()
+                .contains(&std::thread::current().id())
+        );
+    }
+
+    #[test]
+    fn idle_workers_steal_local_tasks() {
+        let executor = Arc::new(Executor::with_workers(4));
+        let threads = Arc::new(Mutex::new(std::collections::HashSet::new()));
+
+        // everything spawned from a worker lands in that worker's queue,
+        // so the other workers only get to run them by stealing
+        let spawner = executor.clone();
+        let seen = threads.clone();
+        let parent = executor.spawn(async move {
+            let children: std::vec::Vec<_> = (0..16)
+                .map(|_| {
+                    let seen = seen.clone();
+                    spawner.spawn(async move { busy(seen) })
+                })
+                .collect();
+            for child in children {
+                child.await;
+            }
+        });
+
+        parent.join().unwrap();
+        assert!(threads.lock().unwrap().len() > 1);
+    }
+
+    #[test]
+    fn parked_workers_wake_up() {
+        let executor = Executor::with_workers(2);
+
+        // give the workers time to go to sleep
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(executor.spawn(async { 4 }).join(), Some(4));
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(executor.spawn(async { 5 }).join(), Some(5));
+    }
+
+    /// sets its flag when it's dropped
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    /// a task that never completes, and sets `dropped` once its future is dropped
+    fn pending_forever(dropped: &Arc<AtomicBool>) -> impl Future<Output = ()> + Send + 'static {
+        let flag = DropFlag(dropped.clone());
+        async move {
+            let _flag = flag;
+            core::future::pending::<()>().await
+        }
+    }
+
+    #[test]
+    fn shutdown_lets_tasks_complete() {
+        let executor = Executor::new_unsync();
+        let done = Rc::new(core::cell::Cell::new(false));
+
+        let flag = done.clone();
+        executor
+            .spawn(async move {
+                time::sleep(Duration::from_millis(10)).await;
+                flag.set(true);
+            })
+            .detach();
+
+        executor.shutdown(Duration::from_secs(10));
+        assert!(done.get());
+    }
+
+    #[test]
+    fn shutdown_cancels_tasks_after_the_timeout() {
+        let executor = Executor::with_workers(1);
+        let dropped = Arc::new(AtomicBool::new(false));
+        let handle = executor.spawn(pending_forever(&dropped));
+
+        let start = std::time::Instant::now();
+        executor.shutdown(Duration::from_millis(20));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        assert!(dropped.load(Ordering::Acquire));
+        assert_eq!(handle.join(), None);
+    }
+
+    #[test]
+    fn spawns_are_rejected_while_shutting_down() {
+        let executor = Executor::with_workers(1);
+        let ready = Arc::new(AtomicBool::new(false));
+        let waker = Arc::new(Mutex::new(None));
+        let draining = executor.spawn(WaitFor {
+            ready: ready.clone(),
+            waker: waker.clone(),
+        });
+
+        std::thread::scope(|s| {
+            s.spawn(|| executor.drain(None));
+            while !executor.inner.task_queue.is_closed() {
+                std::thread::yield_now();
+            }
+            assert_eq!(executor.spawn(async { 1 }).join(), None);
+
+            // tasks spawned before keep running, and are woken up as usual
+            while waker.lock().unwrap().is_none() {
+                std::thread::yield_now();
+            }
+            ready.store(true, Ordering::Release);
+            waker.lock().unwrap().take().unwrap().wake();
+        });
+        assert_eq!(draining.join(), Some(()));
+    }
+
+    #[test]
+    fn dropping_cancels_pending_tasks() {
+        let executor = Executor::new_unsync();
+        let dropped = Arc::new(AtomicBool::new(false));
+        executor.spawn(pending_forever(&dropped)).detach();
+        executor.run_until_stalled();
+
+        drop(executor);
+        assert!(dropped.load(Ordering::Acquire));
+    }
+
+    /// the order `count` tasks run in on an executor seeded with `seed`
+    fn seeded_order(seed: u64, count: usize) -> std::vec::Vec<usize> {
+        let executor = Executor::new_unsync().with_seed(seed);
+        let order = Rc::new(core::cell::RefCell::new(std::vec::Vec::new()));
+        for i in 0..count {
+            let order = order.clone();
+            executor
+                .spawn(async move { order.borrow_mut().push(i) })
+                .detach();
+        }
+
+        assert_eq!(executor.run_until_stalled(), count);
+        order.take()
+    }
+
+    #[test]
+    fn seeded_order_is_reproducible() {
+        let order = seeded_order(7, 16);
+        assert_eq!(seeded_order(7, 16), order);
+        assert_ne!(order, (0..16).collect::<std::vec::Vec<_>>());
+        assert_ne!(seeded_order(8, 16), order);
+    }
+
+    #[test]
+    fn tick_frame_stops_at_the_budget() {
+        let clock = time::MockClock::new();
+        let executor = Executor::with_clock(clock.clone());
+        let handles: std::vec::Vec<_> = (0..10)
+            .map(|_| {
+                let clock = clock.clone();
+                executor.spawn(async move { clock.advance(Duration::from_millis(5)) })
+            })
+            .collect();
+
+        assert_eq!(executor.run_for(clock.now()), 10);
+
+        // the tasks end at 5, 10 and 15ms, and the third one overshoots the budget
+        assert_eq!(executor.tick_frame(Duration::from_millis(12)), 7);
+
+        assert_eq!(executor.tick_frame(Duration::from_secs(10)), 0);
+        for handle in handles {
+            assert!(handle.join().is_some());
+        }
+    }
+
+    #[test]
+    fn tick_frame_returns_with_a_task_that_wakes_itself() {
+        let executor = Executor::with_clock(time::MockClock::new());
+        let polls = Rc::new(core::cell::Cell::new(0));
+
+        let counter = polls.clone();
+        executor
+            .spawn(core::future::poll_fn(move |cx| {
+                counter.set(counter.get() + 1);
+                cx.waker().wake_by_ref();
+                Poll::<()>::Pending
+            }))
+            .detach();
+
+        assert_eq!(executor.tick_frame(Duration::from_millis(16)), 1);
+        assert_eq!(polls.get(), 1);
+        assert_eq!(executor.tick_frame(Duration::from_millis(16)), 1);
+        assert_eq!(polls.get(), 2);
+    }
 }
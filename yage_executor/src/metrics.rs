@@ -0,0 +1,197 @@
+//! counters describing what an executor has been up to, see `Executor::metrics`.
+//!
+//! they are only ever updated with relaxed atomics, so they are cheap enough to always keep,
+//! but a snapshot isn't necessarily consistent with itself: a task can show up as completed
+//! before it shows up as spawned
+
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// how many buckets a `Histogram` has
+const BUCKETS: usize = 24;
+
+/// a snapshot of the metrics of an executor
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// tasks spawned, including the ones spawned by `Executor::spawn_blocking`
+    pub spawned: u64,
+    /// tasks that ran to completion
+    pub completed: u64,
+    /// tasks that were dropped before they completed, either because they were cancelled or because they panicked
+    pub cancelled: u64,
+    /// tasks that are queued up to run right now, not counting the ones waiting for the blocking pool
+    pub queue_depth: usize,
+    /// how many times the futures of tasks were polled
+    pub polls: u64,
+    /// how many times each task that completed or was cancelled was polled
+    pub polls_per_task: Histogram,
+    /// how long polls took, in microseconds
+    pub poll_duration: Histogram,
+    /// tasks that idle workers took out of the queues of other workers
+    pub steals: u64,
+    /// how many I/O events the driver got every time it polled for them
+    pub events_per_tick: Histogram,
+    /// the time the threads of the executor spent parked, added up
+    pub time_parked: Duration,
+}
+
+/// how often values fell into a range, with a bucket for every power of two.
+///
+/// bucket `0` counts zeroes, and bucket `i` counts the values in `2^(i - 1)..2^i`.
+/// the last bucket counts everything above that as well
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+}
+
+impl Histogram {
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    /// the values bucket `index` counts
+    pub fn bucket_range(index: usize) -> Range<u64> {
+        match index {
+            0 => 0..1,
+            _ if index == BUCKETS - 1 => 1 << (index - 1)..u64::MAX,
+            _ => 1 << (index - 1)..1 << index,
+        }
+    }
+
+    /// how many values were recorded
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+}
+
+/// a `Histogram` that can be recorded to from any thread
+pub(crate) struct AtomicHistogram {
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl AtomicHistogram {
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: core::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    pub(crate) fn record(&self, value: u64) {
+        let index = (u64::BITS - value.leading_zeros()) as usize;
+        self.buckets[index.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Histogram {
+        Histogram {
+            buckets: core::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// the metrics about the tasks themselves, which are kept by the executor
+pub(crate) struct TaskMetrics {
+    spawned: AtomicU64,
+    completed: AtomicU64,
+    cancelled: AtomicU64,
+    polls: AtomicU64,
+    polls_per_task: AtomicHistogram,
+    poll_duration: AtomicHistogram,
+}
+
+impl TaskMetrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            spawned: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+            polls: AtomicU64::new(0),
+            polls_per_task: AtomicHistogram::new(),
+            poll_duration: AtomicHistogram::new(),
+        }
+    }
+
+    pub(crate) fn spawned(&self) {
+        self.spawned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn polled(&self, duration: Duration) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.poll_duration
+            .record(duration.as_micros().try_into().unwrap_or(u64::MAX));
+    }
+
+    /// records a task whose future was dropped after being polled `polls` times
+    pub(crate) fn dropped(&self, polls: u64, completed: bool) {
+        let counter = if completed {
+            &self.completed
+        } else {
+            &self.cancelled
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.polls_per_task.record(polls);
+    }
+
+    /// fills in everything in `metrics` that is about the tasks
+    pub(crate) fn snapshot(&self, metrics: &mut Metrics) {
+        metrics.spawned = self.spawned.load(Ordering::Relaxed);
+        metrics.completed = self.completed.load(Ordering::Relaxed);
+        metrics.cancelled = self.cancelled.load(Ordering::Relaxed);
+        metrics.polls = self.polls.load(Ordering::Relaxed);
+        metrics.polls_per_task = self.polls_per_task.snapshot();
+        metrics.poll_duration = self.poll_duration.snapshot();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    use crate::Executor;
+    use crate::time::sleep;
+
+    #[test]
+    fn values_land_in_their_bucket() {
+        let histogram = AtomicHistogram::new();
+        for value in [0, 1, 2, 3, 4, 1000, u64::MAX] {
+            histogram.record(value);
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count(), 7);
+        assert_eq!(&snapshot.buckets()[..4], [1, 1, 2, 1]);
+        assert_eq!(snapshot.buckets()[10], 1);
+        assert_eq!(snapshot.buckets()[BUCKETS - 1], 1);
+
+        assert_eq!(Histogram::bucket_range(0), 0..1);
+        assert_eq!(Histogram::bucket_range(3), 4..8);
+        assert!(Histogram::bucket_range(10).contains(&1000));
+    }
+
+    #[test]
+    fn tasks_are_counted() {
+        let executor = Executor::new_unsync();
+        let first = executor.spawn(async { 1 });
+        let second = executor.spawn(async {
+            sleep(Duration::from_millis(5)).await;
+        });
+        let third = executor.spawn(core::future::pending::<()>());
+        assert_eq!(executor.metrics().queue_depth, 3);
+
+        assert_eq!(executor.block_on(first), 1);
+        executor.block_on(second);
+        third.cancel();
+        executor.run_until_stalled();
+
+        let metrics = executor.metrics();
+        assert_eq!(metrics.spawned, 3);
+        assert_eq!(metrics.completed, 2);
+        assert_eq!(metrics.cancelled, 1);
+        assert_eq!(metrics.queue_depth, 0);
+        assert_eq!(metrics.polls, 4);
+        assert_eq!(metrics.poll_duration.count(), 4);
+        assert_eq!(&metrics.polls_per_task.buckets()[..3], [0, 2, 1]);
+        assert!(metrics.time_parked >= Duration::from_millis(1));
+        assert!(metrics.events_per_tick.count() > 0);
+    }
+}
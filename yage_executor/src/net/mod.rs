@@ -0,0 +1,83 @@
+//! sockets whose operations wait for the driver of the executor instead of blocking the thread.
+//!
+//! they are registered with the driver of the executor running the task that creates them,
+//! so creating one outside of an executor panics. they can be used from any task after that,
+//! and keep working for as long as that executor does
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::task::{Context, Poll};
+use std::io;
+use std::os::fd::AsRawFd;
+use yage_net::{Interest, notifier::SourceFd};
+
+use crate::context;
+use crate::driver::{IoDriver, Registration};
+
+mod tcp;
+mod udp;
+
+pub use tcp::{TcpListener, TcpStream};
+pub use udp::UdpSocket;
+
+/// a nonblocking socket registered with a driver, which is deregistered when this is dropped
+struct Source<T: AsRawFd> {
+    io: T,
+    registration: Registration,
+}
+
+impl<T: AsRawFd> Source<T> {
+    /// registers `io` with the driver of the current executor
+    fn new(io: T) -> io::Result<Self> {
+        let driver = current_driver()?;
+        let interest = Interest::READABLE | Interest::WRITABLE | Interest::READ_CLOSED;
+        let registration = Registration::new(&driver, &mut SourceFd(&io.as_raw_fd()), interest)?;
+        Ok(Self { io, registration })
+    }
+
+    fn poll_read_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        mut f: impl FnMut(&T) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        self.registration.poll_read_io(cx, || f(&self.io))
+    }
+
+    fn poll_write_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        mut f: impl FnMut(&T) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        self.registration.poll_write_io(cx, || f(&self.io))
+    }
+
+    async fn async_io<R>(
+        &self,
+        interest: Interest,
+        mut f: impl FnMut(&T) -> io::Result<R>,
+    ) -> io::Result<R> {
+        self.registration.async_io(interest, || f(&self.io)).await
+    }
+}
+
+impl<T: AsRawFd> Drop for Source<T> {
+    fn drop(&mut self) {
+        // fails once the driver has shut down, which already forgot about every source
+        let _ = self
+            .registration
+            .deregister(&mut SourceFd(&self.io.as_raw_fd()));
+    }
+}
+
+impl<T: AsRawFd + fmt::Debug> fmt::Debug for Source<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.io.fmt(f)
+    }
+}
+
+/// # Panics
+/// if this isn't called from a task run by an `Executor`
+fn current_driver() -> io::Result<Arc<IoDriver>> {
+    context::with_current(|inner| inner.driver.get_or_init().cloned())
+        .expect("a socket was created outside of an executor")
+}
@@ -0,0 +1,245 @@
+use core::task::{Context, Poll};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr};
+use yage_net::{Interest, net};
+
+use super::Source;
+
+/// a TCP socket listening for connections
+#[derive(Debug)]
+pub struct TcpListener {
+    source: Source<net::TcpListener>,
+}
+
+impl TcpListener {
+    /// binds a listener to `addr`
+    ///
+    /// # Panics
+    /// if this isn't called from a task run by an `Executor`
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(Self {
+            source: Source::new(net::TcpListener::bind(addr)?)?,
+        })
+    }
+
+    /// waits for a connection
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (stream, addr) = self
+            .source
+            .async_io(Interest::READABLE, net::TcpListener::accept)
+            .await?;
+        Ok((TcpStream::new(stream)?, addr))
+    }
+
+    /// polls for a connection. only the last task to poll this is woken
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        let (stream, addr) =
+            core::task::ready!(self.source.poll_read_io(cx, net::TcpListener::accept))?;
+        Poll::Ready(TcpStream::new(stream).map(|stream| (stream, addr)))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.source.io.local_addr()
+    }
+}
+
+/// a TCP connection.
+///
+/// everything takes `&self`, so a task can read while another one writes
+#[derive(Debug)]
+pub struct TcpStream {
+    source: Source<net::TcpStream>,
+}
+
+impl TcpStream {
+    /// connects to `addr`
+    ///
+    /// # Panics
+    /// if this isn't called from a task run by an `Executor`
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let stream = Self::new(net::TcpStream::connect(addr)?)?;
+
+        // the socket becomes writable once it's connected, or once connecting failed
+        core::future::poll_fn(|cx| stream.poll_write_ready(cx)).await?;
+        if let Some(e) = stream.source.io.take_error()? {
+            return Err(e);
+        }
+        Ok(stream)
+    }
+
+    fn new(stream: net::TcpStream) -> io::Result<Self> {
+        Ok(Self {
+            source: Source::new(stream)?,
+        })
+    }
+
+    /// reads into `buf`, returning how much was read. `0` means the peer won't send anything else
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.source
+            .async_io(Interest::READABLE, |mut io| io.read(buf))
+            .await
+    }
+
+    /// writes some of `buf`, returning how much was written
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.source
+            .async_io(Interest::WRITABLE, |mut io| io.write(buf))
+            .await
+    }
+
+    /// writes all of `buf`
+    pub async fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// polls for reading into `buf`. only the last task to poll this is woken
+    pub fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.source.poll_read_io(cx, |mut io| io.read(buf))
+    }
+
+    /// polls for writing some of `buf`. only the last task to poll this is woken
+    pub fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.source.poll_write_io(cx, |mut io| io.write(buf))
+    }
+
+    /// polls for the stream to become readable, without reading anything
+    pub fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.source.registration.poll_read_ready(cx).map_ok(drop)
+    }
+
+    /// polls for the stream to become writable, without writing anything
+    pub fn poll_write_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.source.registration.poll_write_ready(cx).map_ok(drop)
+    }
+
+    /// shuts down the reading half, the writing half, or both
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.source.io.shutdown(how)
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.source.io.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.source.io.local_addr()
+    }
+
+    /// sets `TCP_NODELAY`, see `yage_net::TcpStream::set_nodelay`
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.source.io.set_nodelay(nodelay)
+    }
+
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.source.io.nodelay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Executor;
+    use crate::time::timeout;
+    use std::time::Duration;
+
+    fn loopback() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    #[test]
+    fn echo_over_loopback() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let listener = TcpListener::bind(loopback()).unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let server = executor.spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 64];
+                loop {
+                    match stream.read(&mut buf).await.unwrap() {
+                        0 => break,
+                        n => stream.write_all(&buf[..n]).await.unwrap(),
+                    }
+                }
+            });
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+            stream.write_all(b"hello").await.unwrap();
+            let mut buf = [0; 5];
+            let mut read = 0;
+            while read < buf.len() {
+                read += stream.read(&mut buf[read..]).await.unwrap();
+            }
+            assert_eq!(&buf, b"hello");
+
+            stream.shutdown(Shutdown::Write).unwrap();
+            server.await;
+            assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn reads_wait_for_data() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let listener = TcpListener::bind(loopback()).unwrap();
+            let client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (server, _) = listener.accept().await.unwrap();
+
+            let mut buf = [0; 4];
+            let short = Duration::from_millis(20);
+            assert!(timeout(short, server.read(&mut buf)).await.is_err());
+
+            client.write_all(b"data").await.unwrap();
+            let read = timeout(Duration::from_secs(5), server.read(&mut buf)).await;
+            assert_eq!(read.unwrap().unwrap(), 4);
+        });
+    }
+
+    #[test]
+    fn connecting_to_a_closed_port_fails() {
+        let executor = Executor::new_unsync();
+        let addr = executor.block_on(async { TcpListener::bind(loopback()).unwrap().local_addr() });
+        let result = executor.block_on(TcpStream::connect(addr.unwrap()));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn workers_drive_sockets() {
+        let executor = Executor::with_workers(2);
+        let listener = executor
+            .spawn(async { TcpListener::bind(loopback()) })
+            .join()
+            .unwrap()
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = executor.spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            stream.write_all(b"hi").await.unwrap();
+        });
+        let client = executor.spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut buf = [0; 2];
+            let mut read = 0;
+            while read < buf.len() {
+                read += stream.read(&mut buf[read..]).await.unwrap();
+            }
+            buf
+        });
+
+        server.join().unwrap();
+        assert_eq!(&client.join().unwrap(), b"hi");
+    }
+}
@@ -0,0 +1,178 @@
+use core::task::{Context, Poll};
+use std::io;
+use std::net::SocketAddr;
+use yage_net::{Interest, net};
+
+use super::Source;
+
+/// a UDP socket.
+///
+/// everything takes `&self`, so a task can receive while another one sends
+#[derive(Debug)]
+pub struct UdpSocket {
+    source: Source<net::UdpSocket>,
+}
+
+impl UdpSocket {
+    /// binds a socket to `addr`
+    ///
+    /// # Panics
+    /// if this isn't called from a task run by an `Executor`
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Self::from_net(net::UdpSocket::bind(addr)?)
+    }
+
+    /// registers a socket from `yage_net`, which is how one bound with `yage_net::UdpSocket::bind_reusable` is used
+    ///
+    /// # Panics
+    /// if this isn't called from a task run by an `Executor`
+    pub fn from_net(socket: net::UdpSocket) -> io::Result<Self> {
+        Ok(Self {
+            source: Source::new(socket)?,
+        })
+    }
+
+    /// the socket this wraps, for setting options like broadcast and multicast
+    pub fn get_ref(&self) -> &net::UdpSocket {
+        &self.source.io
+    }
+
+    /// sets the only address `send` sends to and `recv` receives from
+    pub fn connect(&self, addr: SocketAddr) -> io::Result<()> {
+        self.source.io.connect(addr)
+    }
+
+    /// sends `buf` as a single datagram to `target`, returning how much of it was sent
+    pub async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        self.source
+            .async_io(Interest::WRITABLE, |io| io.send_to(buf, target))
+            .await
+    }
+
+    /// receives a single datagram into `buf`, returning its size and where it came from.
+    /// whatever doesn't fit in `buf` is discarded
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.source
+            .async_io(Interest::READABLE, |io| io.recv_from(buf))
+            .await
+    }
+
+    /// sends `buf` to the address this is connected to
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.source
+            .async_io(Interest::WRITABLE, |io| io.send(buf))
+            .await
+    }
+
+    /// receives a datagram from the address this is connected to
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.source
+            .async_io(Interest::READABLE, |io| io.recv(buf))
+            .await
+    }
+
+    /// sends every datagram to its address at once, see `yage_net::UdpSocket::send_mmsg`
+    pub async fn send_mmsg(&self, datagrams: &[(&[u8], SocketAddr)]) -> io::Result<usize> {
+        self.source
+            .async_io(Interest::WRITABLE, |io| io.send_mmsg(datagrams))
+            .await
+    }
+
+    /// receives as many datagrams at once as there are, up to one for each of `bufs`,
+    /// see `yage_net::UdpSocket::recv_mmsg`
+    pub async fn recv_mmsg(
+        &self,
+        bufs: &mut [&mut [u8]],
+        received: &mut Vec<(usize, SocketAddr)>,
+    ) -> io::Result<usize> {
+        self.source
+            .async_io(Interest::READABLE, |io| io.recv_mmsg(bufs, received))
+            .await
+    }
+
+    /// polls for sending `buf` to `target`. only the last task to poll for sending is woken
+    pub fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        self.source.poll_write_io(cx, |io| io.send_to(buf, target))
+    }
+
+    /// polls for receiving a datagram. only the last task to poll for receiving is woken
+    pub fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        self.source.poll_read_io(cx, |io| io.recv_from(buf))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.source.io.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.source.io.peer_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Executor;
+
+    #[test]
+    fn datagrams_are_exchanged() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let loopback: SocketAddr = "127.0.0.1:0".parse().unwrap();
+            let a = UdpSocket::bind(loopback).unwrap();
+            let b = UdpSocket::bind(loopback).unwrap();
+            let b_addr = b.local_addr().unwrap();
+
+            let receiver = executor.spawn(async move {
+                let mut buf = [0; 16];
+                let (n, from) = b.recv_from(&mut buf).await.unwrap();
+                b.connect(from).unwrap();
+                b.send(&buf[..n]).await.unwrap();
+            });
+
+            a.send_to(b"state", b_addr).await.unwrap();
+            a.connect(b_addr).unwrap();
+            let mut buf = [0; 16];
+            assert_eq!(a.recv(&mut buf).await.unwrap(), 5);
+            assert_eq!(&buf[..5], b"state");
+            receiver.await;
+        });
+    }
+
+    #[test]
+    fn batches_wait_for_datagrams() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let loopback: SocketAddr = "127.0.0.1:0".parse().unwrap();
+            let sender =
+                UdpSocket::from_net(net::UdpSocket::bind_reusable(loopback).unwrap()).unwrap();
+            let receiver = UdpSocket::bind(loopback).unwrap();
+            let addr = receiver.local_addr().unwrap();
+            assert!(sender.get_ref().reuse_port().unwrap());
+
+            let mut storage = [[0; 8]; 4];
+            let mut received = Vec::new();
+            let batch = executor.spawn(async move {
+                let mut bufs: Vec<&mut [u8]> = storage.iter_mut().map(|buf| &mut buf[..]).collect();
+                receiver.recv_mmsg(&mut bufs, &mut received).await.unwrap();
+                received
+            });
+
+            let datagrams: [(&[u8], _); 2] = [(b"a", addr), (b"b", addr)];
+            assert_eq!(sender.send_mmsg(&datagrams).await.unwrap(), 2);
+            let received = batch.await;
+            assert!(!received.is_empty());
+            assert!(received.iter().all(|&(len, _)| len == 1));
+        });
+    }
+}
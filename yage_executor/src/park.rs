@@ -0,0 +1,148 @@
+#[cfg(feature = "std")]
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+
+#[cfg(feature = "std")]
+use crate::driver::DriverCell;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// puts the thread running the executor to sleep until there is something to do.
+///
+/// under `std` this parks the thread, otherwise it spins.
+/// once the driver is created, the thread drives it instead, unless another thread is already doing that
+pub(crate) struct Parker {
+    notified: AtomicBool,
+    #[cfg(feature = "std")]
+    thread: std::sync::Mutex<Option<std::thread::Thread>>,
+    #[cfg(feature = "std")]
+    driver: DriverCell,
+    /// how long this has spent parked, in nanoseconds, shared with the parkers from `Parker::sibling`
+    #[cfg(feature = "std")]
+    parked: Arc<AtomicU64>,
+}
+
+impl Parker {
+    pub(crate) fn new(#[cfg(feature = "std")] driver: DriverCell) -> Self {
+        Self {
+            notified: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            thread: std::sync::Mutex::new(None),
+            #[cfg(feature = "std")]
+            driver,
+            #[cfg(feature = "std")]
+            parked: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// a parker for another thread, which shares the driver with this one,
+    /// and adds the time it spends parked to that of this one
+    pub(crate) fn sibling(&self) -> Self {
+        Self {
+            notified: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            thread: std::sync::Mutex::new(None),
+            #[cfg(feature = "std")]
+            driver: self.driver.clone(),
+            #[cfg(feature = "std")]
+            parked: self.parked.clone(),
+        }
+    }
+
+    /// blocks until `Parker::unpark` is called.
+    /// returns right away if it was called since the last time this returned.
+    ///
+    /// while driving, this can also return once an I/O event came in or a timer fired
+    pub(crate) fn park(&self) {
+        #[cfg(feature = "std")]
+        self.park_until(None);
+
+        #[cfg(not(feature = "std"))]
+        while !self.notified.swap(false, Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// the same as `Parker::park`, but returns after `timeout` at the latest
+    #[cfg(feature = "std")]
+    pub(crate) fn park_timeout(&self, timeout: Duration) {
+        self.park_until(Instant::now().checked_add(timeout));
+    }
+
+    #[cfg(feature = "std")]
+    fn park_until(&self, deadline: Option<Instant>) {
+        let start = Instant::now();
+        self.wait_until(deadline);
+        let parked = start.elapsed().as_nanos().try_into().unwrap_or(u64::MAX);
+        self.parked.fetch_add(parked, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "std")]
+    fn wait_until(&self, deadline: Option<Instant>) {
+        if let Some(driver) = self.driver.get()
+            && driver.park(
+                &self.notified,
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+            )
+        {
+            return;
+        }
+
+        *self.thread.lock().unwrap() = Some(std::thread::current());
+
+        while !self.notified.swap(false, Ordering::Acquire) {
+            match deadline {
+                None => std::thread::park(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return;
+                    }
+                    std::thread::park_timeout(deadline - now);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn time_parked(&self) -> Duration {
+        Duration::from_nanos(self.parked.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn unpark(&self) {
+        // `SeqCst` pairs with `IoDriver::park`.
+        // if it was already notified, whoever did that woke the thread up
+        #[cfg(feature = "std")]
+        if !self.notified.swap(true, Ordering::SeqCst) {
+            if let Some(driver) = self.driver.get() {
+                driver.unpark();
+            }
+            if let Some(thread) = &*self.thread.lock().unwrap() {
+                thread.unpark();
+            }
+        }
+
+        #[cfg(not(feature = "std"))]
+        self.notified.store(true, Ordering::SeqCst);
+    }
+}
+
+/// the waker of a future passed to `Executor::block_on`
+pub(crate) struct BlockOn {
+    pub(crate) woken: AtomicBool,
+    pub(crate) parker: Arc<Parker>,
+}
+
+impl Wake for BlockOn {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.parker.unpark();
+    }
+}
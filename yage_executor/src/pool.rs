@@ -0,0 +1,196 @@
+//! the worker threads of an `Executor<ThreadSafe>`.
+//!
+//! every worker has its own queue, which tasks woken on that worker are pushed to.
+//! tasks woken anywhere else go to the shared injector queue of the executor.
+//! a worker that runs out of tasks first checks the injector, then steals half of the queue of another worker,
+//! and parks once there is nothing left anywhere
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use concurrent_queue::{ConcurrentQueue, PushError};
+use std::sync::Mutex;
+use yage_task::task::Task;
+
+use crate::driver::DriverCell;
+use crate::park::Parker;
+
+pub(crate) struct Pool {
+    workers: Box<[Worker]>,
+    /// the workers that are parked (or about to be)
+    idle: Mutex<Vec<usize>>,
+    shutdown: AtomicBool,
+    /// how many tasks were stolen, for `Executor::metrics`
+    steals: AtomicU64,
+    driver: DriverCell,
+}
+
+struct Worker {
+    queue: ConcurrentQueue<Task<usize>>,
+    parker: Parker,
+}
+
+std::thread_local! {
+    /// the pool and index of the worker running on this thread
+    static CURRENT: Cell<(*const Pool, usize)> = const { Cell::new((core::ptr::null(), 0)) };
+}
+
+impl Pool {
+    pub(crate) fn new(workers: usize, driver: &DriverCell) -> Self {
+        Self {
+            workers: (0..workers)
+                .map(|_| Worker {
+                    queue: ConcurrentQueue::unbounded(),
+                    parker: Parker::new(driver.clone()),
+                })
+                .collect(),
+            idle: Mutex::new(Vec::with_capacity(workers)),
+            shutdown: AtomicBool::new(false),
+            steals: AtomicU64::new(0),
+            driver: driver.clone(),
+        }
+    }
+
+    /// pushes `task` to the queue of the current worker.
+    /// hands it back if this isn't being called from one of the workers of this pool
+    pub(crate) fn push_local(&self, task: Task<usize>) -> Result<(), Task<usize>> {
+        let (pool, index) = CURRENT.with(Cell::get);
+        if !core::ptr::eq(pool, self) {
+            return Err(task);
+        }
+
+        // worker queues are only closed once the workers are gone
+        self.workers[index]
+            .queue
+            .push(task)
+            .map_err(|(PushError::Closed(task) | PushError::Full(task))| task)?;
+        self.notify_one();
+        Ok(())
+    }
+
+    /// wakes up a parked worker, if there is one
+    pub(crate) fn notify_one(&self) {
+        let index = self.idle.lock().unwrap().pop();
+        if let Some(index) = index {
+            self.workers[index].parker.unpark();
+        }
+    }
+
+    /// wakes up every worker, so they catch up on the signals they have to block
+    pub(crate) fn unpark_all(&self) {
+        for worker in &self.workers {
+            worker.parker.unpark();
+        }
+    }
+
+    /// tells every worker to stop once it's done with its current task
+    pub(crate) fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        for worker in &self.workers {
+            worker.queue.close();
+            worker.parker.unpark();
+        }
+    }
+
+    /// takes a task out of the queue of any worker.
+    /// this is used to drop the tasks that are left after shutting down
+    pub(crate) fn pop_any(&self) -> Option<Task<usize>> {
+        self.workers
+            .iter()
+            .find_map(|worker| worker.queue.pop().ok())
+    }
+
+    /// how many tasks are queued up on the workers
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.workers.iter().map(|worker| worker.queue.len()).sum()
+    }
+
+    pub(crate) fn steals(&self) -> u64 {
+        self.steals.load(Ordering::Relaxed)
+    }
+
+    /// the time the workers spent parked, added up
+    pub(crate) fn time_parked(&self) -> Duration {
+        self.workers
+            .iter()
+            .map(|worker| worker.parker.time_parked())
+            .sum()
+    }
+
+    /// the main loop of worker `index`
+    pub(crate) fn run(&self, index: usize, injector: &ConcurrentQueue<Task<usize>>) {
+        CURRENT.with(|current| current.set((self, index)));
+        let worker = &self.workers[index];
+
+        while !self.shutdown.load(Ordering::Acquire) {
+            self.driver.block_signals();
+            if let Some(task) = self.find_task(index, injector) {
+                task.run();
+                continue;
+            }
+
+            self.idle.lock().unwrap().push(index);
+
+            // something could have been pushed before we were on the idle list,
+            // in which case nobody is going to wake us up for it
+            if !self.has_tasks(injector) && !self.shutdown.load(Ordering::Acquire) {
+                worker.parker.park();
+            }
+
+            // whoever woke us up already took us off the list, unless we never went to sleep
+            self.idle.lock().unwrap().retain(|&i| i != index);
+        }
+
+        CURRENT.with(|current| current.set((core::ptr::null(), 0)));
+    }
+
+    fn find_task(
+        &self,
+        index: usize,
+        injector: &ConcurrentQueue<Task<usize>>,
+    ) -> Option<Task<usize>> {
+        if let Ok(task) = self.workers[index].queue.pop() {
+            return Some(task);
+        }
+        if let Ok(task) = injector.pop() {
+            return Some(task);
+        }
+        self.steal(index)
+    }
+
+    /// steals half of the tasks of the first worker that has any, returning one of them
+    fn steal(&self, index: usize) -> Option<Task<usize>> {
+        let count = self.workers.len();
+        let own = &self.workers[index].queue;
+
+        for offset in 1..count {
+            let victim = &self.workers[(index + offset) % count].queue;
+
+            let mut first = None;
+            for _ in 0..victim.len().div_ceil(2) {
+                let Ok(task) = victim.pop() else { break };
+                self.steals.fetch_add(1, Ordering::Relaxed);
+                match first {
+                    None => first = Some(task),
+                    // this only fails once we're shutting down, and the task is dropped either way
+                    Some(_) => {
+                        let _ = own.push(task);
+                    }
+                }
+            }
+
+            if first.is_some() {
+                return first;
+            }
+        }
+
+        None
+    }
+
+    fn has_tasks(&self, injector: &ConcurrentQueue<Task<usize>>) -> bool {
+        !injector.is_empty() || self.workers.iter().any(|worker| !worker.queue.is_empty())
+    }
+}
@@ -0,0 +1,217 @@
+//! listening for unix signals, like the `SIGTERM` a server gets when it's asked to shut down.
+//!
+//! signals are read from a `signalfd` kept by the driver of the executor, so they have to be blocked
+//! on every thread of the process, or they are handled the default way instead.
+//! nothing is blocked until `signal` is first called for a kind of signal, which blocks it on the
+//! calling thread, and on the threads the executor spawns, as they wake up. it stays blocked on them
+//! for as long as they live, even once nothing listens for it anymore. threads spawned any other way
+//! are left alone, and only block it if they are spawned after it's blocked by the thread spawning them.
+//!
+//! a thread of the executor that is busy running a task or a blocking closure when `signal` is called
+//! only blocks the signal once it's done with that, and gets it the default way until then.
+//!
+//! a signal sent to the process is read by whichever thread drives the executor,
+//! but a signal sent to a single thread can only be read by that thread
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::task::{Context, Poll};
+use std::io;
+
+use crate::context;
+use crate::driver::IoDriver;
+
+/// the signals that can be listened for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SignalKind {
+    /// `SIGINT`, sent by the terminal on ctrl-c
+    Interrupt,
+    /// `SIGTERM`, the polite way to ask a process to exit
+    Terminate,
+    /// `SIGHUP`, sent when the terminal goes away, and by convention to reload configuration
+    Hangup,
+}
+
+impl SignalKind {
+    pub(crate) const ALL: [SignalKind; 3] = [
+        SignalKind::Interrupt,
+        SignalKind::Terminate,
+        SignalKind::Hangup,
+    ];
+
+    /// the number of the signal
+    pub fn as_raw(self) -> i32 {
+        match self {
+            SignalKind::Interrupt => libc::SIGINT,
+            SignalKind::Terminate => libc::SIGTERM,
+            SignalKind::Hangup => libc::SIGHUP,
+        }
+    }
+}
+
+/// starts listening for `kind`, which is blocked on the calling thread and the threads of the executor
+/// from here on, even after the returned `Signal` is dropped.
+///
+/// # Panics
+/// if this isn't called from a task run by an `Executor`
+pub fn signal(kind: SignalKind) -> io::Result<Signal> {
+    let driver = context::with_current(|inner| {
+        let driver = inner.driver.get_or_init()?.clone();
+        if driver.add_signal(kind)? {
+            // they block it as they wake up
+            inner.pool.unpark_all();
+            inner.blocking.wake_idle();
+        }
+        io::Result::Ok(driver)
+    })
+    .expect("a signal was listened for outside of an executor")?;
+
+    Ok(Signal {
+        seen: driver.signals().received(kind),
+        driver,
+        kind,
+        key: None,
+    })
+}
+
+/// the signals of a kind received since this was created by `signal`.
+///
+/// receiving the same signal several times before `Signal::recv` is called only counts once
+pub struct Signal {
+    driver: Arc<IoDriver>,
+    kind: SignalKind,
+    /// how many signals had been received the last time this returned one
+    seen: u64,
+    /// the key of the waker of this in the driver, once it has been polled
+    key: Option<usize>,
+}
+
+impl Signal {
+    pub fn kind(&self) -> SignalKind {
+        self.kind
+    }
+
+    /// waits for the next signal, returning `None` once the executor has shut down
+    pub async fn recv(&mut self) -> Option<()> {
+        core::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// polls for the next signal, returning `None` once the executor has shut down
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        core::task::ready!(crate::coop::poll_proceed(cx));
+        self.driver
+            .signals()
+            .poll_recv(self.kind, &mut self.seen, &mut self.key, cx)
+    }
+}
+
+impl Drop for Signal {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.driver.signals().remove(self.kind, key);
+        }
+    }
+}
+
+impl fmt::Debug for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signal")
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Executor;
+    use crate::time::timeout;
+    use std::time::Duration;
+
+    fn raise(kind: SignalKind) {
+        // sent to the calling thread, which has it blocked, so this can't take down the test process
+        assert_eq!(unsafe { libc::raise(kind.as_raw()) }, 0);
+    }
+
+    /// whether `kind` is blocked on the calling thread
+    fn is_blocked(kind: SignalKind) -> bool {
+        unsafe {
+            let mut mask = core::mem::zeroed();
+            libc::pthread_sigmask(libc::SIG_BLOCK, core::ptr::null(), &mut mask);
+            libc::sigismember(&mask, kind.as_raw()) == 1
+        }
+    }
+
+    #[test]
+    fn threads_only_block_signals_that_are_listened_for() {
+        let mut executor = Executor::with_workers(1);
+        executor.spawn_reactor().unwrap();
+        let on_worker = |executor: &Executor<crate::ThreadSafe>| {
+            executor
+                .spawn(async { is_blocked(SignalKind::Hangup) })
+                .join()
+                .unwrap()
+        };
+        let on_blocking = |executor: &Executor<crate::ThreadSafe>| {
+            executor
+                .spawn_blocking(|| is_blocked(SignalKind::Hangup))
+                .join()
+                .unwrap()
+        };
+
+        assert!(!on_worker(&executor));
+        assert!(!on_blocking(&executor));
+
+        // listened for on another thread, which is the only one outside of the executor that blocks it
+        let _hangup = std::thread::scope(|s| {
+            s.spawn(|| executor.block_on(async { signal(SignalKind::Hangup) }))
+                .join()
+                .unwrap()
+                .unwrap()
+        });
+        assert!(on_worker(&executor));
+        assert!(on_blocking(&executor));
+        assert!(!is_blocked(SignalKind::Hangup));
+    }
+
+    #[test]
+    fn signals_are_received() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let mut hangup = signal(SignalKind::Hangup).unwrap();
+            let mut other = signal(SignalKind::Hangup).unwrap();
+            raise(SignalKind::Hangup);
+
+            assert_eq!(hangup.recv().await, Some(()));
+            assert_eq!(other.recv().await, Some(()));
+        });
+    }
+
+    #[test]
+    fn repeated_signals_are_coalesced() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let mut terminate = signal(SignalKind::Terminate).unwrap();
+            let mut hangup = signal(SignalKind::Hangup).unwrap();
+            raise(SignalKind::Terminate);
+            raise(SignalKind::Terminate);
+
+            assert_eq!(terminate.recv().await, Some(()));
+            let short = Duration::from_millis(20);
+            assert!(timeout(short, terminate.recv()).await.is_err());
+            assert!(timeout(short, hangup.recv()).await.is_err());
+        });
+    }
+
+    #[test]
+    fn listeners_see_shutdown() {
+        let executor = Executor::new_unsync();
+        let mut interrupt = executor.block_on(async { signal(SignalKind::Interrupt).unwrap() });
+        drop(executor);
+
+        let waker = std::task::Waker::noop();
+        let poll = interrupt.poll_recv(&mut Context::from_waker(waker));
+        assert_eq!(poll, Poll::Ready(None));
+    }
+}
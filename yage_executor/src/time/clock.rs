@@ -0,0 +1,189 @@
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::driver::IoDriver;
+
+/// where an executor gets the time from
+#[derive(Clone, Debug, Default)]
+pub enum Clock {
+    /// the monotonic clock of the system
+    #[default]
+    System,
+    /// a clock that only moves when it's told to, see `MockClock`
+    Mock(MockClock),
+}
+
+impl Clock {
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::System => Instant::now(),
+            Clock::Mock(clock) => clock.now(),
+        }
+    }
+
+    pub(crate) fn is_mock(&self) -> bool {
+        matches!(self, Clock::Mock(_))
+    }
+
+    /// lets `advance` fire the timers of `driver`, if this is a `MockClock`
+    pub(crate) fn attach(&self, driver: &Arc<IoDriver>) {
+        if let Clock::Mock(clock) = self {
+            clock
+                .inner
+                .drivers
+                .lock()
+                .unwrap()
+                .push(Arc::downgrade(driver));
+        }
+    }
+}
+
+impl From<MockClock> for Clock {
+    fn from(clock: MockClock) -> Self {
+        Clock::Mock(clock)
+    }
+}
+
+/// a clock that stands still until `MockClock::advance` is called, for deterministic tests.
+///
+/// timers of an executor using this only fire when it's advanced, and they fire right away,
+/// so a test can step through as many frames as it likes without sleeping.
+/// clones share the same time
+#[derive(Clone)]
+pub struct MockClock {
+    inner: Arc<MockInner>,
+}
+
+struct MockInner {
+    now: Mutex<Instant>,
+    /// the drivers of the executors using this clock
+    drivers: Mutex<Vec<Weak<IoDriver>>>,
+}
+
+impl MockClock {
+    /// creates a clock that starts at the current time of the system
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    pub fn starting_at(now: Instant) -> Self {
+        Self {
+            inner: Arc::new(MockInner {
+                now: Mutex::new(now),
+                drivers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        *self.inner.now.lock().unwrap()
+    }
+
+    /// moves the clock forward by `duration`, and wakes the tasks waiting on every timer that is now due.
+    /// they still have to be run by their executor
+    pub fn advance(&self, duration: Duration) {
+        *self.inner.now.lock().unwrap() += duration;
+
+        let drivers: Vec<_> = {
+            let mut drivers = self.inner.drivers.lock().unwrap();
+            drivers.retain(|driver| driver.strong_count() > 0);
+            drivers.iter().filter_map(Weak::upgrade).collect()
+        };
+        for driver in drivers {
+            driver.process_timers();
+        }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MockClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockClock")
+            .field("now", &self.now())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Executor;
+    use crate::time::{interval, sleep};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn timers_fire_when_advanced() {
+        let clock = MockClock::new();
+        let executor = Executor::with_clock(clock.clone());
+        let start = Instant::now();
+
+        let done = Rc::new(Cell::new(false));
+        let flag = done.clone();
+        executor
+            .spawn(async move {
+                sleep(Duration::from_secs(60)).await;
+                flag.set(true);
+            })
+            .detach();
+        executor.run_until_stalled();
+
+        clock.advance(Duration::from_secs(60) - MS);
+        executor.run_until_stalled();
+        assert!(!done.get());
+
+        clock.advance(MS);
+        executor.run_until_stalled();
+        assert!(done.get());
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn frames_run_faster_than_real_time() {
+        let clock = MockClock::new();
+        let executor = Executor::with_clock(clock.clone());
+        let ticks = Rc::new(Cell::new(0));
+
+        let counter = ticks.clone();
+        executor
+            .spawn(async move {
+                let mut interval = interval(16 * MS);
+                loop {
+                    interval.tick().await;
+                    counter.set(counter.get() + 1);
+                }
+            })
+            .detach();
+
+        let start = Instant::now();
+        for _ in 0..100 {
+            executor.tick_frame(16 * MS);
+            clock.advance(16 * MS);
+        }
+        executor.tick_frame(16 * MS);
+
+        assert_eq!(ticks.get(), 101);
+        assert!(start.elapsed() < 100 * 16 * MS);
+    }
+
+    #[test]
+    fn tasks_see_the_mock_time() {
+        let clock = MockClock::new();
+        let executor = Executor::with_clock(clock.clone());
+
+        clock.advance(Duration::from_secs(5));
+        let now = executor.block_on(async { crate::time::now() });
+        assert_eq!(now, clock.now());
+    }
+}
@@ -0,0 +1,488 @@
+//! waiting for a point in time, without blocking the thread.
+//!
+//! timers are kept by the driver of the executor running the task that polls them,
+//! which is created the first time one is polled. they fire with a resolution of a millisecond,
+//! and never early.
+//!
+//! everything here goes by the `Clock` of that executor, see `MockClock` for tests.
+//! outside of an executor, it goes by the system clock
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::future::{Future, IntoFuture};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::context;
+use crate::driver::IoDriver;
+use crate::driver::time::TimerShared;
+
+mod clock;
+
+pub use clock::{Clock, MockClock};
+
+/// the current time, according to the clock of the executor running this
+pub fn now() -> Instant {
+    context::with_current(|inner| inner.driver.clock().now()).unwrap_or_else(Instant::now)
+}
+
+/// waits until `duration` has passed
+pub fn sleep(duration: Duration) -> Sleep {
+    // a duration that doesn't fit is as good as forever
+    let deadline = now().checked_add(duration).unwrap_or_else(far_future);
+    sleep_until(deadline)
+}
+
+/// waits until `deadline`
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        entry: None,
+    }
+}
+
+/// roughly 30 years from now
+fn far_future() -> Instant {
+    now() + Duration::from_secs(86400 * 365 * 30)
+}
+
+/// the future returned by `sleep` and `sleep_until`.
+///
+/// polling this outside of a task run by an `Executor` panics
+pub struct Sleep {
+    deadline: Instant,
+    /// the driver the timer is registered with, once it has been polled
+    entry: Option<(Arc<IoDriver>, Arc<TimerShared>)>,
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// whether the timer has gone off
+    pub fn is_elapsed(&self) -> bool {
+        match &self.entry {
+            Some((_, timer)) => timer.has_fired(),
+            None => now() >= self.deadline,
+        }
+    }
+
+    /// makes this wait until `deadline` instead, even if it has already gone off
+    pub fn reset(&mut self, deadline: Instant) {
+        self.deadline = deadline;
+        if let Some((driver, timer)) = &self.entry {
+            driver.register_timer(timer, deadline);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        core::task::ready!(crate::coop::poll_proceed(cx));
+        let this = &mut *self;
+
+        let (_, timer) = match &this.entry {
+            Some(entry) => entry,
+            None => {
+                if now() >= this.deadline {
+                    return Poll::Ready(());
+                }
+
+                let driver = context::with_current(|inner| inner.driver.get_or_init().cloned())
+                    .expect("a timer was polled outside of an executor")
+                    .expect("failed to create the driver");
+                let timer = Arc::new(TimerShared::new());
+                driver.register_timer(&timer, this.deadline);
+                this.entry.insert((driver, timer))
+            }
+        };
+
+        timer.register_waker(cx.waker());
+        if timer.has_fired() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((driver, timer)) = &self.entry {
+            driver.deregister_timer(timer);
+        }
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .field("is_elapsed", &self.is_elapsed())
+            .finish()
+    }
+}
+
+/// runs `future` for at most `duration`
+pub fn timeout<F: IntoFuture>(duration: Duration, future: F) -> Timeout<F::IntoFuture> {
+    Timeout {
+        future: future.into_future(),
+        sleep: sleep(duration),
+    }
+}
+
+/// runs `future` until `deadline` at the latest
+pub fn timeout_at<F: IntoFuture>(deadline: Instant, future: F) -> Timeout<F::IntoFuture> {
+    Timeout {
+        future: future.into_future(),
+        sleep: sleep_until(deadline),
+    }
+}
+
+/// the future returned by `timeout` and `timeout_at`.
+///
+/// if the future completes in the same poll the deadline passes in, its output wins
+#[derive(Debug)]
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.future
+    }
+
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of while pinned, and `Sleep` is `Unpin`
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        if let Poll::Ready(out) = future.poll(cx) {
+            return Poll::Ready(Ok(out));
+        }
+        Pin::new(&mut this.sleep)
+            .poll(cx)
+            .map(|()| Err(Elapsed(())))
+    }
+}
+
+/// the error returned by `Timeout` when the deadline passed before the future completed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// what an `Interval` does when it is polled late enough to have missed ticks.
+/// a tick counts as missed once it's more than 5ms late
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// ticks right away until it has caught up, so it ticks as often as it would have without falling behind
+    #[default]
+    Burst,
+    /// ticks right away once, and then every period from there
+    Delay,
+    /// skips the missed ticks, and ticks at the next multiple of the period from the start
+    Skip,
+}
+
+impl MissedTickBehavior {
+    /// when to tick next, after ticking `now` for the tick that was due at `timeout`
+    fn next_timeout(self, timeout: Instant, now: Instant, period: Duration) -> Instant {
+        match self {
+            MissedTickBehavior::Burst => timeout + period,
+            MissedTickBehavior::Delay => now + period,
+            MissedTickBehavior::Skip => {
+                let late = (now - timeout).as_nanos() % period.as_nanos();
+                now + period - Duration::from_nanos(late as u64)
+            }
+        }
+    }
+}
+
+/// ticks every `period`, starting right away.
+///
+/// # Panics
+/// if `period` is zero
+pub fn interval(period: Duration) -> Interval {
+    interval_at(now(), period)
+}
+
+/// ticks every `period`, starting at `start`.
+///
+/// # Panics
+/// if `period` is zero
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(!period.is_zero(), "an interval can't have a period of zero");
+    Interval {
+        sleep: sleep_until(start),
+        period,
+        missed_tick_behavior: MissedTickBehavior::default(),
+    }
+}
+
+/// ticks at a fixed period, created by `interval` and `interval_at`
+#[derive(Debug)]
+pub struct Interval {
+    sleep: Sleep,
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+impl Interval {
+    /// waits for the next tick, returning when it was due
+    pub async fn tick(&mut self) -> Instant {
+        core::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// polls for the next tick, returning when it was due
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.sleep).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let timeout = self.sleep.deadline();
+        let now = now();
+        let next = if now > timeout + Duration::from_millis(5) {
+            self.missed_tick_behavior
+                .next_timeout(timeout, now, self.period)
+        } else {
+            timeout + self.period
+        };
+        self.sleep.reset(next);
+
+        Poll::Ready(timeout)
+    }
+
+    /// makes the next tick a period from now
+    pub fn reset(&mut self) {
+        self.sleep.reset(now() + self.period);
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Executor;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn sleep_waits_for_the_duration() {
+        let executor = Executor::new_unsync();
+        let start = Instant::now();
+        executor.block_on(sleep(20 * MS));
+        assert!(start.elapsed() >= 20 * MS);
+    }
+
+    #[test]
+    fn sleeps_finish_in_order() {
+        let executor = Executor::new_unsync();
+        let order = Rc::new(RefCell::new(Vec::new()));
+
+        let handles: Vec<_> = [30, 10, 20]
+            .into_iter()
+            .map(|ms| {
+                let order = order.clone();
+                executor.spawn(async move {
+                    sleep(ms * MS).await;
+                    order.borrow_mut().push(ms);
+                })
+            })
+            .collect();
+        for handle in handles {
+            executor.block_on(handle);
+        }
+
+        assert_eq!(*order.borrow(), [10, 20, 30]);
+    }
+
+    #[test]
+    fn workers_fire_timers() {
+        let executor = Executor::with_workers(2);
+        let start = Instant::now();
+
+        // nothing calls `block_on`, so an idle worker has to drive the timers
+        let handle = executor.spawn(async { sleep(15 * MS).await });
+        handle.join().unwrap();
+        assert!(start.elapsed() >= 15 * MS);
+    }
+
+    #[test]
+    fn timeout_elapses() {
+        let executor = Executor::new_unsync();
+
+        let slow = timeout(10 * MS, sleep(Duration::from_secs(10)));
+        assert_eq!(executor.block_on(slow), Err(Elapsed(())));
+
+        let fast = timeout(Duration::from_secs(10), async { 4 });
+        assert_eq!(executor.block_on(fast), Ok(4));
+    }
+
+    #[test]
+    fn reset_after_firing() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let mut sleep = sleep(5 * MS);
+            (&mut sleep).await;
+            assert!(sleep.is_elapsed());
+
+            let deadline = Instant::now() + 10 * MS;
+            sleep.reset(deadline);
+            assert!(!sleep.is_elapsed());
+            sleep.await;
+            assert!(Instant::now() >= deadline);
+        });
+    }
+
+    /// polls `sleep` once, which registers it
+    async fn register(sleep: &mut Sleep) {
+        core::future::poll_fn(|cx| {
+            assert!(Pin::new(&mut *sleep).poll(cx).is_pending());
+            Poll::Ready(())
+        })
+        .await
+    }
+
+    #[test]
+    fn dropped_sleeps_leave_the_wheel() {
+        let executor = Executor::new_unsync();
+        let hour = Duration::from_secs(3600);
+        executor.block_on(async {
+            let mut kept = sleep(hour);
+            register(&mut kept).await;
+
+            for _ in 0..1000 {
+                let mut sleep = sleep(hour);
+                register(&mut sleep).await;
+            }
+            for minutes in 1..100 {
+                kept.reset(now() + hour + minutes * 60 * 1000 * MS);
+            }
+            assert_eq!(executor.io_driver().unwrap().num_timers(), 1);
+        });
+
+        assert_eq!(executor.io_driver().unwrap().num_timers(), 0);
+    }
+
+    #[test]
+    fn interval_ticks_every_period() {
+        let executor = Executor::new_unsync();
+        executor.block_on(async {
+            let mut interval = interval(10 * MS);
+            let first = interval.tick().await;
+            let second = interval.tick().await;
+            let third = interval.tick().await;
+
+            assert_eq!(second - first, 10 * MS);
+            assert_eq!(third - second, 10 * MS);
+            assert!(Instant::now() >= third);
+        });
+    }
+
+    #[test]
+    fn missed_ticks() {
+        let start = Instant::now();
+        let period = 10 * MS;
+        let now = start + 35 * MS;
+
+        assert_eq!(
+            MissedTickBehavior::Burst.next_timeout(start, now, period),
+            start + 10 * MS
+        );
+        assert_eq!(
+            MissedTickBehavior::Delay.next_timeout(start, now, period),
+            start + 45 * MS
+        );
+        assert_eq!(
+            MissedTickBehavior::Skip.next_timeout(start, now, period),
+            start + 40 * MS
+        );
+    }
+
+    #[test]
+    fn interval_catches_up_after_falling_behind() {
+        let clock = MockClock::new();
+        let executor = Executor::with_clock(clock.clone());
+        let burst = Rc::new(RefCell::new(Vec::new()));
+        let delay = Rc::new(RefCell::new(Vec::new()));
+
+        let ticks = burst.clone();
+        executor
+            .spawn(async move {
+                let mut interval = interval(10 * MS);
+                loop {
+                    let tick = interval.tick().await;
+                    ticks.borrow_mut().push(tick);
+                }
+            })
+            .detach();
+        let ticks = delay.clone();
+        executor
+            .spawn(async move {
+                let mut interval = interval(10 * MS);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    let tick = interval.tick().await;
+                    ticks.borrow_mut().push(tick);
+                }
+            })
+            .detach();
+        executor.run_until_stalled();
+        let start = clock.now();
+
+        clock.advance(35 * MS);
+        executor.run_until_stalled();
+
+        // the ticks due at 10, 20 and 30ms are all ready
+        assert_eq!(*burst.borrow(), [0, 10, 20, 30].map(|ms| start + ms * MS));
+        // only the one due at 10ms is, and the next is a period after that one was late
+        assert_eq!(*delay.borrow(), [start, start + 10 * MS]);
+
+        clock.advance(9 * MS);
+        executor.run_until_stalled();
+        assert_eq!(delay.borrow().len(), 2);
+
+        clock.advance(MS);
+        executor.run_until_stalled();
+        assert_eq!(delay.borrow()[2], start + 45 * MS);
+    }
+}
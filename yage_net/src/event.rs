@@ -0,0 +1,187 @@
+//! the readiness events a selector reports for its registered sources
+
+use core::fmt;
+
+use crate::Token;
+
+/// a registered source becoming ready, as reported by `EventLoop::poll`
+#[repr(transparent)]
+pub struct Event {
+    inner: libc::epoll_event,
+}
+
+impl Event {
+    fn from_sys(event: &libc::epoll_event) -> &Event {
+        // SAFETY: `Event` is a transparent wrapper around `epoll_event`
+        unsafe { &*(event as *const libc::epoll_event).cast::<Event>() }
+    }
+
+    /// the token the source was registered as
+    pub fn token(&self) -> Token {
+        Token(self.inner.u64 as usize)
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.has(libc::EPOLLIN | libc::EPOLLPRI)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.has(libc::EPOLLOUT)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.has(libc::EPOLLERR)
+    }
+
+    /// whether the peer shut down its writing half, or the source hung up altogether
+    pub fn is_read_closed(&self) -> bool {
+        self.has(libc::EPOLLHUP | libc::EPOLLRDHUP)
+    }
+
+    /// whether nothing can be written to the source anymore
+    pub fn is_write_closed(&self) -> bool {
+        self.has(libc::EPOLLHUP) || (self.has(libc::EPOLLOUT) && self.has(libc::EPOLLERR))
+    }
+
+    /// whether out-of-band data can be read
+    pub fn is_priority(&self) -> bool {
+        self.has(libc::EPOLLPRI)
+    }
+
+    fn has(&self, flags: libc::c_int) -> bool {
+        // `epoll_event` is packed, so the field is copied out
+        let events = self.inner.events;
+        events & flags as u32 != 0
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Event")
+            .field("token", &self.token())
+            .field("readable", &self.is_readable())
+            .field("writable", &self.is_writable())
+            .field("error", &self.is_error())
+            .field("read_closed", &self.is_read_closed())
+            .field("write_closed", &self.is_write_closed())
+            .field("priority", &self.is_priority())
+            .finish()
+    }
+}
+
+/// the buffer `EventLoop::poll` fills with events, which holds up to its capacity at once
+pub struct Events {
+    inner: Vec<libc::epoll_event>,
+}
+
+impl Events {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// how many events the last poll got
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.inner.iter(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    pub(crate) fn sys(&mut self) -> &mut Vec<libc::epoll_event> {
+        &mut self.inner
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a Events {
+    type Item = &'a Event;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// the events in `Events`, see `Events::iter`
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    inner: core::slice::Iter<'a, libc::epoll_event>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Event;
+
+    fn next(&mut self) -> Option<&'a Event> {
+        self.inner.next().map(Event::from_sys)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event_loop::EventLoop;
+    use crate::{Interest, Trigger, notifier::SourceFd};
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    #[test]
+    fn events_describe_the_readiness() {
+        let mut event_loop = EventLoop::new().unwrap();
+        let (a, mut b) = UnixStream::pair().unwrap();
+        let interests = Interest::READABLE | Interest::WRITABLE | Interest::READ_CLOSED;
+        event_loop
+            .registry()
+            .register(
+                &mut SourceFd(&a.as_raw_fd()),
+                Token(7),
+                interests,
+                Trigger::Level,
+            )
+            .unwrap();
+
+        let mut events = Events::with_capacity(4);
+        event_loop.poll(&mut events, Some(Duration::ZERO)).unwrap();
+        let event = events.iter().next().unwrap();
+        assert_eq!(event.token(), Token(7));
+        assert!(event.is_writable() && !event.is_readable());
+
+        b.write_all(&[1]).unwrap();
+        drop(b);
+        event_loop.poll(&mut events, Some(Duration::ZERO)).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = events.iter().next().unwrap();
+        assert!(event.is_readable() && event.is_read_closed());
+        assert!(!event.is_error() && !event.is_priority());
+    }
+}
@@ -1,6 +1,7 @@
-use crate::{Interest, Token, notifier::Notifier, unix::selector::Selector};
+use crate::{
+    Interest, Token, Trigger, event::Events, notifier::Notifier, unix::selector::Selector,
+};
 use std::{
-    env::Vars,
     io,
     sync::{
         Arc,
@@ -32,33 +33,41 @@ impl EventLoop {
         &self.registry
     }
 
-    pub fn poll(
-        &mut self,
-        events: &mut Vec<libc::epoll_event>,
-        timeout: Option<Duration>,
-    ) -> io::Result<()> {
-        self.registry.selector.select(events, timeout)
+    /// waits for at most `timeout`, forever if it's `None`, for registered sources to become ready,
+    /// replacing whatever `events` held with them
+    pub fn poll(&mut self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+        self.registry.selector.select(events.sys(), timeout)
     }
 }
 
 impl Registry {
-    pub fn register<N>(&self, notifier: &mut N, token: Token, interests: Interest) -> io::Result<()>
+    /// registers `notifier` as `token`, to be reported once it's ready for any of `interests`
+    pub fn register<N>(
+        &self,
+        notifier: &mut N,
+        token: Token,
+        interests: Interest,
+        trigger: Trigger,
+    ) -> io::Result<()>
     where
         N: Notifier + ?Sized,
     {
-        notifier.register(self, token, interests)
+        notifier.register(self, token, interests, trigger)
     }
 
+    /// changes the token, interests and trigger of an already registered `notifier`,
+    /// which also rearms a `Trigger::Oneshot` registration
     pub fn reregister<N>(
         &self,
         notifier: &mut N,
         token: Token,
         interests: Interest,
+        trigger: Trigger,
     ) -> io::Result<()>
     where
         N: Notifier + ?Sized,
     {
-        notifier.reregister(self, token, interests)
+        notifier.reregister(self, token, interests, trigger)
     }
 
     pub fn deregister<N>(&self, notifier: &mut N) -> io::Result<()>
@@ -68,6 +77,7 @@ impl Registry {
         notifier.deregister(self)
     }
 
+    /// panics if a waker was already registered, since a registry only has room for one
     pub(crate) fn register_waker(&self) {
         assert!(!self.has_waker.swap(true, Ordering::AcqRel), "no.")
     }
@@ -79,5 +89,3 @@ impl Registry {
         })
     }
 }
-
-pub type Events = Vec<libc::epoll_event>;
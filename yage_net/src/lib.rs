@@ -1,14 +1,86 @@
+pub mod event;
 pub mod event_loop;
+pub mod net;
 pub mod notifier;
 mod unix;
 pub mod waker;
 
 use core::num::NonZero;
+use core::ops::{BitOr, BitOrAssign};
 
+pub use net::{TcpListener, TcpStream, UdpSocket};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Token(pub usize);
 
+/// the readiness a source is registered for, which can be combined with `|`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Interest(NonZero<u8>);
 
 impl Interest {
-    pub const READABLE: Self = unsafe { Self(NonZero::new_unchecked(0b0001)) };
+    pub const READABLE: Self = Self(NonZero::new(0b0001).unwrap());
+    pub const WRITABLE: Self = Self(NonZero::new(0b0010).unwrap());
+    /// out-of-band data, like TCP urgent data
+    pub const PRIORITY: Self = Self(NonZero::new(0b0100).unwrap());
+    /// the peer shutting down its writing half of a stream
+    pub const READ_CLOSED: Self = Self(NonZero::new(0b1000).unwrap());
+
+    /// the interests in both `self` and `other`
+    #[must_use]
+    pub const fn add(self, other: Self) -> Self {
+        // SAFETY: the union of two non-empty sets isn't empty
+        Self(unsafe { NonZero::new_unchecked(self.0.get() | other.0.get()) })
+    }
+
+    /// `self` without the interests in `other`, or `None` if that leaves nothing
+    #[must_use]
+    pub const fn remove(self, other: Self) -> Option<Self> {
+        match NonZero::new(self.0.get() & !other.0.get()) {
+            Some(interest) => Some(Self(interest)),
+            None => None,
+        }
+    }
+
+    pub const fn is_readable(self) -> bool {
+        self.0.get() & Self::READABLE.0.get() != 0
+    }
+
+    pub const fn is_writable(self) -> bool {
+        self.0.get() & Self::WRITABLE.0.get() != 0
+    }
+
+    pub const fn is_priority(self) -> bool {
+        self.0.get() & Self::PRIORITY.0.get() != 0
+    }
+
+    pub const fn is_read_closed(self) -> bool {
+        self.0.get() & Self::READ_CLOSED.0.get() != 0
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.add(rhs)
+    }
+}
+
+impl BitOrAssign for Interest {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.add(rhs);
+    }
+}
+
+/// when the selector reports a registered source as ready
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// once every time its readiness changes, so it has to be used until it would block
+    /// before it's reported again
+    #[default]
+    Edge,
+    /// every time the selector is polled, for as long as it's ready
+    Level,
+    /// only once, after which it has to be reregistered to be reported again
+    Oneshot,
 }
@@ -0,0 +1,81 @@
+//! nonblocking sockets, which are registered with a `Registry` to find out when they're ready.
+//!
+//! every operation returns `WouldBlock` instead of waiting, after which the socket has to be
+//! reported ready by the selector before it's worth trying again
+
+/// implements `Notifier`, `fmt::Debug` and the fd traits for a socket type with an `inner` std socket
+macro_rules! socket_impls {
+    ($ty:ty, $std:ty) => {
+        impl $crate::notifier::Notifier for $ty {
+            fn register(
+                &mut self,
+                registry: &$crate::event_loop::Registry,
+                token: $crate::Token,
+                interests: $crate::Interest,
+                trigger: $crate::Trigger,
+            ) -> std::io::Result<()> {
+                $crate::notifier::SourceFd(&std::os::fd::AsRawFd::as_raw_fd(self))
+                    .register(registry, token, interests, trigger)
+            }
+
+            fn reregister(
+                &mut self,
+                registry: &$crate::event_loop::Registry,
+                token: $crate::Token,
+                interests: $crate::Interest,
+                trigger: $crate::Trigger,
+            ) -> std::io::Result<()> {
+                $crate::notifier::SourceFd(&std::os::fd::AsRawFd::as_raw_fd(self))
+                    .reregister(registry, token, interests, trigger)
+            }
+
+            fn deregister(
+                &mut self,
+                registry: &$crate::event_loop::Registry,
+            ) -> std::io::Result<()> {
+                $crate::notifier::SourceFd(&std::os::fd::AsRawFd::as_raw_fd(self))
+                    .deregister(registry)
+            }
+        }
+
+        impl std::fmt::Debug for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Debug::fmt(&self.inner, f)
+            }
+        }
+
+        impl std::os::fd::AsRawFd for $ty {
+            fn as_raw_fd(&self) -> std::os::fd::RawFd {
+                std::os::fd::AsRawFd::as_raw_fd(&self.inner)
+            }
+        }
+
+        impl std::os::fd::AsFd for $ty {
+            fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+                std::os::fd::AsFd::as_fd(&self.inner)
+            }
+        }
+
+        impl std::os::fd::IntoRawFd for $ty {
+            fn into_raw_fd(self) -> std::os::fd::RawFd {
+                std::os::fd::IntoRawFd::into_raw_fd(self.inner)
+            }
+        }
+
+        /// the fd is assumed to be nonblocking already
+        impl std::os::fd::FromRawFd for $ty {
+            unsafe fn from_raw_fd(fd: std::os::fd::RawFd) -> Self {
+                Self {
+                    // SAFETY: upheld by the caller
+                    inner: unsafe { <$std as std::os::fd::FromRawFd>::from_raw_fd(fd) },
+                }
+            }
+        }
+    };
+}
+
+mod tcp;
+mod udp;
+
+pub use tcp::{TcpListener, TcpStream};
+pub use udp::UdpSocket;
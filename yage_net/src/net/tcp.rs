@@ -0,0 +1,287 @@
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::net::{self, Shutdown, SocketAddr};
+use std::os::fd::{AsRawFd, OwnedFd};
+
+use crate::unix::net as sys;
+
+/// how many connections can wait to be accepted
+const BACKLOG: libc::c_int = 1024;
+
+/// a nonblocking TCP socket listening for connections
+pub struct TcpListener {
+    inner: net::TcpListener,
+}
+
+impl TcpListener {
+    /// binds a listener to `addr`, with `SO_REUSEADDR` set so it can be rebound right after it's closed
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let socket = sys::new_socket(&addr, libc::SOCK_STREAM)?;
+        let fd = socket.as_raw_fd();
+        sys::set_option(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+        sys::bind(fd, &addr)?;
+        sys::listen(fd, BACKLOG)?;
+
+        Ok(Self {
+            inner: socket.into(),
+        })
+    }
+
+    /// takes a listener from std, making it nonblocking
+    pub fn from_std(listener: net::TcpListener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(Self { inner: listener })
+    }
+
+    /// accepts a connection, which is nonblocking as well
+    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (socket, addr) = sys::accept(self.inner.as_raw_fd())?;
+        Ok((TcpStream::from_owned(socket), addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// takes the pending error on the socket, if there is one
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+}
+
+/// a nonblocking TCP connection
+pub struct TcpStream {
+    inner: net::TcpStream,
+}
+
+impl TcpStream {
+    /// starts connecting to `addr`.
+    ///
+    /// this returns before the connection is established. once the stream is reported writable,
+    /// `TcpStream::take_error` says whether it failed, and `TcpStream::peer_addr` succeeds if it didn't
+    pub fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let socket = sys::new_socket(&addr, libc::SOCK_STREAM)?;
+        sys::connect(socket.as_raw_fd(), &addr)?;
+        Ok(Self::from_owned(socket))
+    }
+
+    /// takes a stream from std, making it nonblocking
+    pub fn from_std(stream: net::TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(Self { inner: stream })
+    }
+
+    fn from_owned(socket: OwnedFd) -> Self {
+        Self {
+            inner: socket.into(),
+        }
+    }
+
+    /// the address this is connected to, or a `NotConnected` error while it's still connecting
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// shuts down the reading half, the writing half, or both
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    /// sets `TCP_NODELAY`, which sends small writes right away instead of batching them up,
+    /// at the cost of more packets
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.inner.nodelay()
+    }
+
+    /// reads into `buf` without taking the data out of the receive queue
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.peek(buf)
+    }
+
+    /// takes the pending error on the socket, if there is one
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        (&*self).read_vectored(bufs)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        (&*self).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Read for &TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.inner).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        (&self.inner).read_vectored(bufs)
+    }
+}
+
+impl Write for &TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.inner).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        (&self.inner).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.inner).flush()
+    }
+}
+
+socket_impls!(TcpListener, net::TcpListener);
+socket_impls!(TcpStream, net::TcpStream);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event::Events;
+    use crate::event_loop::EventLoop;
+    use crate::{Interest, Token, Trigger};
+    use std::time::Duration;
+
+    const LISTENER: Token = Token(0);
+    const CLIENT: Token = Token(1);
+    const SERVER: Token = Token(2);
+
+    fn loopback() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    /// polls until `token` is reported, failing the test if it takes too long
+    fn wait_for(event_loop: &mut EventLoop, events: &mut Events, token: Token) {
+        for _ in 0..100 {
+            event_loop
+                .poll(events, Some(Duration::from_millis(10)))
+                .unwrap();
+            if events.iter().any(|event| event.token() == token) {
+                return;
+            }
+        }
+        panic!("{token:?} was never ready");
+    }
+
+    /// a client and a server connected over loopback, both registered with `event_loop`
+    fn connected(event_loop: &mut EventLoop, events: &mut Events) -> (TcpStream, TcpStream) {
+        let mut listener = TcpListener::bind(loopback()).unwrap();
+        let registry = event_loop.registry();
+        registry
+            .register(&mut listener, LISTENER, Interest::READABLE, Trigger::Edge)
+            .unwrap();
+
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let interests = Interest::READABLE | Interest::WRITABLE | Interest::READ_CLOSED;
+        registry
+            .register(&mut client, CLIENT, interests, Trigger::Edge)
+            .unwrap();
+
+        wait_for(event_loop, events, LISTENER);
+        let (mut server, addr) = listener.accept().unwrap();
+        assert_eq!(addr, client.local_addr().unwrap());
+        let registry = event_loop.registry();
+        registry
+            .register(&mut server, SERVER, interests, Trigger::Edge)
+            .unwrap();
+
+        // the client could have connected in the same poll
+        if !events.iter().any(|event| event.token() == CLIENT) {
+            wait_for(event_loop, events, CLIENT);
+        }
+        assert!(client.take_error().unwrap().is_none());
+        assert_eq!(client.peer_addr().unwrap(), listener.local_addr().unwrap());
+        (client, server)
+    }
+
+    #[test]
+    fn accept_would_block_without_connections() {
+        let listener = TcpListener::bind(loopback()).unwrap();
+        let err = listener.accept().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn streams_exchange_data_over_loopback() {
+        let mut event_loop = EventLoop::new().unwrap();
+        let mut events = Events::with_capacity(8);
+        let (mut client, mut server) = connected(&mut event_loop, &mut events);
+
+        let mut buf = [0; 16];
+        assert_eq!(
+            server.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        client.set_nodelay(true).unwrap();
+        assert!(client.nodelay().unwrap());
+        client.write_all(b"ping").unwrap();
+        wait_for(&mut event_loop, &mut events, SERVER);
+        assert_eq!(server.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"ping");
+
+        server.write_all(b"pong").unwrap();
+        wait_for(&mut event_loop, &mut events, CLIENT);
+        assert_eq!(client.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"pong");
+    }
+
+    #[test]
+    fn shutdown_is_seen_by_the_peer() {
+        let mut event_loop = EventLoop::new().unwrap();
+        let mut events = Events::with_capacity(8);
+        let (client, mut server) = connected(&mut event_loop, &mut events);
+
+        client.shutdown(Shutdown::Write).unwrap();
+        wait_for(&mut event_loop, &mut events, SERVER);
+        let event = events.iter().find(|event| event.token() == SERVER).unwrap();
+        assert!(event.is_read_closed());
+        assert_eq!(server.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn connecting_to_a_closed_port_fails() {
+        let addr = TcpListener::bind(loopback()).unwrap().local_addr().unwrap();
+        let mut event_loop = EventLoop::new().unwrap();
+        let mut events = Events::with_capacity(8);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        event_loop
+            .registry()
+            .register(&mut stream, CLIENT, Interest::WRITABLE, Trigger::Edge)
+            .unwrap();
+        wait_for(&mut event_loop, &mut events, CLIENT);
+
+        assert!(stream.take_error().unwrap().is_some());
+        assert!(stream.peer_addr().is_err());
+    }
+}
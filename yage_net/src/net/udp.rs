@@ -0,0 +1,292 @@
+use std::io;
+use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::AsRawFd;
+
+use crate::unix::net as sys;
+
+/// a nonblocking UDP socket
+pub struct UdpSocket {
+    inner: net::UdpSocket,
+}
+
+impl UdpSocket {
+    /// binds a socket to `addr`
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let socket = sys::new_socket(&addr, libc::SOCK_DGRAM)?;
+        sys::bind(socket.as_raw_fd(), &addr)?;
+        Ok(Self {
+            inner: socket.into(),
+        })
+    }
+
+    /// binds a socket to `addr` with `SO_REUSEADDR` and `SO_REUSEPORT` set,
+    /// so other sockets with them set can bind to the same address.
+    /// the kernel spreads the datagrams sent to it between them, except for multicast and broadcast,
+    /// which every one of them receives
+    pub fn bind_reusable(addr: SocketAddr) -> io::Result<Self> {
+        let socket = sys::new_socket(&addr, libc::SOCK_DGRAM)?;
+        let fd = socket.as_raw_fd();
+        sys::set_option(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+        sys::set_option(fd, libc::SOL_SOCKET, libc::SO_REUSEPORT, 1)?;
+        sys::bind(fd, &addr)?;
+        Ok(Self {
+            inner: socket.into(),
+        })
+    }
+
+    /// takes a socket from std, making it nonblocking
+    pub fn from_std(socket: net::UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self { inner: socket })
+    }
+
+    /// sends `buf` as a single datagram to `target`
+    pub fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        self.inner.send_to(buf, target)
+    }
+
+    /// receives a single datagram into `buf`, returning its size and where it came from.
+    /// whatever doesn't fit in `buf` is discarded
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+
+    /// sets the only address `send` sends to and `recv` receives from
+    pub fn connect(&self, addr: SocketAddr) -> io::Result<()> {
+        self.inner.connect(addr)
+    }
+
+    /// sends `buf` to the address this is connected to
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf)
+    }
+
+    /// receives a datagram from the address this is connected to
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf)
+    }
+
+    /// sends every datagram to its address with a single `sendmmsg`, returning how many of them were sent.
+    /// that can be fewer than there are once the send buffer fills up
+    pub fn send_mmsg(&self, datagrams: &[(&[u8], SocketAddr)]) -> io::Result<usize> {
+        sys::send_mmsg(self.inner.as_raw_fd(), datagrams)
+    }
+
+    /// receives up to a datagram into each of `bufs` with a single `recvmmsg`, returning how many there were.
+    /// the size and address of each of them are put in `received`, which is cleared first
+    pub fn recv_mmsg(
+        &self,
+        bufs: &mut [&mut [u8]],
+        received: &mut Vec<(usize, SocketAddr)>,
+    ) -> io::Result<usize> {
+        sys::recv_mmsg(self.inner.as_raw_fd(), bufs, received)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// the address this is connected to
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// sets `SO_BROADCAST`, which allows sending to a broadcast address
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    pub fn broadcast(&self) -> io::Result<bool> {
+        self.inner.broadcast()
+    }
+
+    /// sets `SO_REUSEADDR`, which only affects sockets that bind after this is set
+    pub fn set_reuse_address(&self, reuse: bool) -> io::Result<()> {
+        sys::set_option(
+            self.inner.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_REUSEADDR,
+            reuse as libc::c_int,
+        )
+    }
+
+    pub fn reuse_address(&self) -> io::Result<bool> {
+        sys::get_option(self.inner.as_raw_fd(), libc::SOL_SOCKET, libc::SO_REUSEADDR)
+            .map(|reuse| reuse != 0)
+    }
+
+    /// sets `SO_REUSEPORT`, which only affects sockets that bind after this is set
+    pub fn set_reuse_port(&self, reuse: bool) -> io::Result<()> {
+        sys::set_option(
+            self.inner.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            reuse as libc::c_int,
+        )
+    }
+
+    pub fn reuse_port(&self) -> io::Result<bool> {
+        sys::get_option(self.inner.as_raw_fd(), libc::SOL_SOCKET, libc::SO_REUSEPORT)
+            .map(|reuse| reuse != 0)
+    }
+
+    /// joins the multicast group `multiaddr` on the interface with the address `interface`,
+    /// or the one the kernel picks if it's unspecified
+    pub fn join_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> io::Result<()> {
+        self.inner.join_multicast_v4(&multiaddr, &interface)
+    }
+
+    pub fn leave_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> io::Result<()> {
+        self.inner.leave_multicast_v4(&multiaddr, &interface)
+    }
+
+    /// joins the multicast group `multiaddr` on the interface with the index `interface`,
+    /// or the one the kernel picks if it's `0`
+    pub fn join_multicast_v6(&self, multiaddr: Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.inner.join_multicast_v6(&multiaddr, interface)
+    }
+
+    pub fn leave_multicast_v6(&self, multiaddr: Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.inner.leave_multicast_v6(&multiaddr, interface)
+    }
+
+    /// sets whether multicast datagrams this sends are looped back to the sockets on this host
+    pub fn set_multicast_loop_v4(&self, on: bool) -> io::Result<()> {
+        self.inner.set_multicast_loop_v4(on)
+    }
+
+    /// sets how many hops multicast datagrams this sends can take
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> io::Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    /// takes the pending error on the socket, if there is one
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+}
+
+socket_impls!(UdpSocket, net::UdpSocket);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event::Events;
+    use crate::event_loop::EventLoop;
+    use crate::{Interest, Token, Trigger};
+    use std::time::Duration;
+
+    fn loopback() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    #[test]
+    fn datagrams_are_received_once_readable() {
+        let mut event_loop = EventLoop::new().unwrap();
+        let mut events = Events::with_capacity(4);
+        let mut receiver = UdpSocket::bind(loopback()).unwrap();
+        let sender = UdpSocket::bind(loopback()).unwrap();
+        event_loop
+            .registry()
+            .register(&mut receiver, Token(0), Interest::READABLE, Trigger::Edge)
+            .unwrap();
+
+        let mut buf = [0; 16];
+        let err = receiver.recv_from(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        let addr = receiver.local_addr().unwrap();
+        sender.send_to(b"tick", addr).unwrap();
+        event_loop
+            .poll(&mut events, Some(Duration::from_secs(5)))
+            .unwrap();
+        assert!(events.iter().any(|event| event.token() == Token(0)));
+        assert_eq!(
+            receiver.recv_from(&mut buf).unwrap(),
+            (4, sender.local_addr().unwrap())
+        );
+        assert_eq!(&buf[..4], b"tick");
+    }
+
+    #[test]
+    fn connected_sockets_send_and_recv() {
+        let a = UdpSocket::bind(loopback()).unwrap();
+        let b = UdpSocket::bind(loopback()).unwrap();
+        a.connect(b.local_addr().unwrap()).unwrap();
+        b.connect(a.local_addr().unwrap()).unwrap();
+        assert_eq!(a.peer_addr().unwrap(), b.local_addr().unwrap());
+
+        a.send(b"ping").unwrap();
+        let mut buf = [0; 4];
+        // loopback delivers before `send` returns
+        assert_eq!(b.recv(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[test]
+    fn options_round_trip() {
+        let socket = UdpSocket::bind(loopback()).unwrap();
+        assert!(!socket.broadcast().unwrap());
+        socket.set_broadcast(true).unwrap();
+        assert!(socket.broadcast().unwrap());
+
+        assert!(!socket.reuse_address().unwrap());
+        socket.set_reuse_address(true).unwrap();
+        assert!(socket.reuse_address().unwrap());
+        socket.set_reuse_port(true).unwrap();
+        assert!(socket.reuse_port().unwrap());
+    }
+
+    #[test]
+    fn reusable_sockets_share_a_port() {
+        let first = UdpSocket::bind_reusable(loopback()).unwrap();
+        let addr = first.local_addr().unwrap();
+        let second = UdpSocket::bind_reusable(addr).unwrap();
+        assert_eq!(second.local_addr().unwrap(), addr);
+
+        let err = UdpSocket::bind(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn multicast_groups_are_joined_and_left() {
+        let socket = UdpSocket::bind("0.0.0.0:0".parse().unwrap()).unwrap();
+        let group = Ipv4Addr::new(239, 255, 42, 1);
+        socket
+            .join_multicast_v4(group, Ipv4Addr::LOCALHOST)
+            .unwrap();
+        socket
+            .leave_multicast_v4(group, Ipv4Addr::LOCALHOST)
+            .unwrap();
+        // it's no longer a member
+        assert!(
+            socket
+                .leave_multicast_v4(group, Ipv4Addr::LOCALHOST)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn batches_are_sent_and_received() {
+        let sender = UdpSocket::bind(loopback()).unwrap();
+        let receiver = UdpSocket::bind(loopback()).unwrap();
+        let addr = receiver.local_addr().unwrap();
+
+        let datagrams: [(&[u8], _); 3] = [(b"a", addr), (b"bb", addr), (b"ccc", addr)];
+        assert_eq!(sender.send_mmsg(&datagrams).unwrap(), 3);
+
+        let mut storage = [[0; 8]; 4];
+        let mut bufs: Vec<&mut [u8]> = storage.iter_mut().map(|buf| &mut buf[..]).collect();
+        let mut received = Vec::new();
+        assert_eq!(receiver.recv_mmsg(&mut bufs, &mut received).unwrap(), 3);
+
+        let from = sender.local_addr().unwrap();
+        assert_eq!(received, [(1, from), (2, from), (3, from)]);
+        assert_eq!(&bufs[2][..3], b"ccc");
+
+        let err = receiver.recv_mmsg(&mut bufs, &mut received).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(received.is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::event_loop;
 use std::io;
+use std::os::fd::RawFd;
 
 pub trait Notifier {
     fn register(
@@ -7,6 +8,7 @@ pub trait Notifier {
         registry: &event_loop::Registry,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: crate::Trigger,
     ) -> io::Result<()>;
 
     fn reregister(
@@ -14,6 +16,7 @@ pub trait Notifier {
         registry: &event_loop::Registry,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: crate::Trigger,
     ) -> io::Result<()>;
 
     fn deregister(&mut self, registry: &event_loop::Registry) -> io::Result<()>;
@@ -28,8 +31,9 @@ where
         registry: &event_loop::Registry,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: crate::Trigger,
     ) -> io::Result<()> {
-        (**self).register(registry, token, interests)
+        (**self).register(registry, token, interests, trigger)
     }
 
     fn reregister(
@@ -37,8 +41,9 @@ where
         registry: &event_loop::Registry,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: crate::Trigger,
     ) -> io::Result<()> {
-        (**self).reregister(registry, token, interests)
+        (**self).reregister(registry, token, interests, trigger)
     }
 
     fn deregister(&mut self, registry: &event_loop::Registry) -> io::Result<()> {
@@ -55,8 +60,9 @@ where
         registry: &event_loop::Registry,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: crate::Trigger,
     ) -> io::Result<()> {
-        T::register(&mut **self, registry, token, interests)
+        T::register(&mut **self, registry, token, interests, trigger)
     }
 
     fn reregister(
@@ -64,11 +70,46 @@ where
         registry: &event_loop::Registry,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: crate::Trigger,
     ) -> io::Result<()> {
-        T::reregister(&mut **self, registry, token, interests)
+        T::reregister(&mut **self, registry, token, interests, trigger)
     }
 
     fn deregister(&mut self, registry: &event_loop::Registry) -> io::Result<()> {
         T::deregister(&mut **self, registry)
     }
 }
+
+/// registers a raw file descriptor, which stays owned by the caller
+#[derive(Debug)]
+pub struct SourceFd<'a>(pub &'a RawFd);
+
+impl Notifier for SourceFd<'_> {
+    fn register(
+        &mut self,
+        registry: &event_loop::Registry,
+        token: crate::Token,
+        interests: crate::Interest,
+        trigger: crate::Trigger,
+    ) -> io::Result<()> {
+        registry
+            .selector
+            .register(*self.0, token, interests, trigger)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &event_loop::Registry,
+        token: crate::Token,
+        interests: crate::Interest,
+        trigger: crate::Trigger,
+    ) -> io::Result<()> {
+        registry
+            .selector
+            .reregister(*self.0, token, interests, trigger)
+    }
+
+    fn deregister(&mut self, registry: &event_loop::Registry) -> io::Result<()> {
+        registry.selector.deregister(*self.0)
+    }
+}
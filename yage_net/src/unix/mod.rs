@@ -1,3 +1,4 @@
+pub(crate) mod net;
 pub mod selector;
 pub mod waker;
 
@@ -0,0 +1,257 @@
+use std::io;
+use std::mem::size_of;
+use std::net::SocketAddr;
+use std::os::fd::{FromRawFd, OwnedFd};
+
+/// creates a nonblocking, close-on-exec socket for `addr`'s family
+pub(crate) fn new_socket(addr: &SocketAddr, ty: libc::c_int) -> io::Result<OwnedFd> {
+    let domain = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    let ty = ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+    let fd = super::wrap_error(|| unsafe {
+        let fd = libc::socket(domain, ty, 0);
+        (fd, fd)
+    })?;
+    // SAFETY: the socket was just created, and nothing else owns it
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// `addr` in the form the socket calls take it
+pub(crate) fn socket_addr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: all zeroes is a valid `sockaddr_storage`
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let sin = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                },
+                sin_zero: [0; 8],
+            };
+            // SAFETY: `sockaddr_storage` is large and aligned enough for any address
+            unsafe { (&raw mut storage).cast::<libc::sockaddr_in>().write(sin) };
+            size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sin6 = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            // SAFETY: as above
+            unsafe { (&raw mut storage).cast::<libc::sockaddr_in6>().write(sin6) };
+            size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    (storage, len as libc::socklen_t)
+}
+
+/// reads back an address filled in by the OS
+pub(crate) fn to_socket_addr(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: the family says it's a `sockaddr_in`
+            let sin =
+                unsafe { &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            let ip = sin.sin_addr.s_addr.to_ne_bytes();
+            Ok(SocketAddr::from((ip, u16::from_be(sin.sin_port))))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the family says it's a `sockaddr_in6`
+            let sin6 = unsafe {
+                &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+            };
+            Ok(SocketAddr::V6(std::net::SocketAddrV6::new(
+                sin6.sin6_addr.s6_addr.into(),
+                u16::from_be(sin6.sin6_port),
+                sin6.sin6_flowinfo,
+                sin6.sin6_scope_id,
+            )))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unsupported address family",
+        )),
+    }
+}
+
+/// sets an integer socket option
+pub(crate) fn set_option(
+    fd: libc::c_int,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    super::wrap_error(|| unsafe {
+        let ret = libc::setsockopt(
+            fd,
+            level,
+            name,
+            (&raw const value).cast(),
+            size_of::<libc::c_int>() as libc::socklen_t,
+        );
+        (ret, ())
+    })
+}
+
+/// gets an integer socket option
+pub(crate) fn get_option(
+    fd: libc::c_int,
+    level: libc::c_int,
+    name: libc::c_int,
+) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+    super::wrap_error(|| unsafe {
+        let ret = libc::getsockopt(fd, level, name, (&raw mut value).cast(), &mut len);
+        (ret, ())
+    })?;
+    Ok(value)
+}
+
+pub(crate) fn bind(fd: libc::c_int, addr: &SocketAddr) -> io::Result<()> {
+    let (storage, len) = socket_addr(addr);
+    super::wrap_error(|| unsafe {
+        let ret = libc::bind(fd, (&raw const storage).cast(), len);
+        (ret, ())
+    })
+}
+
+pub(crate) fn listen(fd: libc::c_int, backlog: libc::c_int) -> io::Result<()> {
+    super::wrap_error(|| unsafe { (libc::listen(fd, backlog), ()) })
+}
+
+/// starts connecting to `addr`, which finishes in the background for a nonblocking socket
+pub(crate) fn connect(fd: libc::c_int, addr: &SocketAddr) -> io::Result<()> {
+    let (storage, len) = socket_addr(addr);
+    match super::wrap_error(|| unsafe {
+        let ret = libc::connect(fd, (&raw const storage).cast(), len);
+        (ret, ())
+    }) {
+        Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => Ok(()),
+        result => result,
+    }
+}
+
+/// accepts a connection as a nonblocking, close-on-exec socket
+pub(crate) fn accept(fd: libc::c_int) -> io::Result<(OwnedFd, SocketAddr)> {
+    // SAFETY: all zeroes is a valid `sockaddr_storage`
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let flags = libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+
+    let fd = super::wrap_error(|| unsafe {
+        let fd = libc::accept4(fd, (&raw mut storage).cast(), &mut len, flags);
+        (fd, fd)
+    })?;
+    // SAFETY: the socket was just accepted, and nothing else owns it
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    Ok((socket, to_socket_addr(&storage)?))
+}
+
+/// a message header for a single datagram in `buf`, with its address in `storage`
+fn msghdr(
+    iov: &mut libc::iovec,
+    storage: &mut libc::sockaddr_storage,
+    len: libc::socklen_t,
+) -> libc::mmsghdr {
+    // SAFETY: all zeroes is a valid `msghdr`, without any control messages
+    let mut hdr: libc::msghdr = unsafe { std::mem::zeroed() };
+    hdr.msg_name = (storage as *mut libc::sockaddr_storage).cast();
+    hdr.msg_namelen = len;
+    hdr.msg_iov = iov;
+    hdr.msg_iovlen = 1;
+    libc::mmsghdr {
+        msg_hdr: hdr,
+        msg_len: 0,
+    }
+}
+
+/// sends every datagram to its address with a single call, returning how many of them were sent
+pub(crate) fn send_mmsg(fd: libc::c_int, datagrams: &[(&[u8], SocketAddr)]) -> io::Result<usize> {
+    if datagrams.is_empty() {
+        return Ok(0);
+    }
+
+    let mut addrs: Vec<_> = datagrams
+        .iter()
+        .map(|(_, addr)| socket_addr(addr))
+        .collect();
+    let mut iovecs: Vec<_> = datagrams
+        .iter()
+        .map(|(buf, _)| libc::iovec {
+            iov_base: buf.as_ptr().cast_mut().cast(),
+            iov_len: buf.len(),
+        })
+        .collect();
+    let mut msgs: Vec<_> = iovecs
+        .iter_mut()
+        .zip(&mut addrs)
+        .map(|(iov, (storage, len))| msghdr(iov, storage, *len))
+        .collect();
+
+    // SAFETY: every header points at an iovec and an address that outlive the call,
+    // and the kernel only reads from the buffers
+    let sent = super::wrap_error(|| unsafe {
+        let ret = libc::sendmmsg(fd, msgs.as_mut_ptr(), msgs.len() as libc::c_uint, 0);
+        (ret, ret)
+    })?;
+    Ok(sent as usize)
+}
+
+/// receives a datagram into each of `bufs` with a single call, without waiting for more than there are.
+/// the size and address of each one are put in `received`, which is cleared first
+pub(crate) fn recv_mmsg(
+    fd: libc::c_int,
+    bufs: &mut [&mut [u8]],
+    received: &mut Vec<(usize, SocketAddr)>,
+) -> io::Result<usize> {
+    received.clear();
+    if bufs.is_empty() {
+        return Ok(0);
+    }
+
+    // SAFETY: all zeroes is a valid `sockaddr_storage`
+    let mut addrs = vec![unsafe { std::mem::zeroed::<libc::sockaddr_storage>() }; bufs.len()];
+    let mut iovecs: Vec<_> = bufs
+        .iter_mut()
+        .map(|buf| libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        })
+        .collect();
+    let len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let mut msgs: Vec<_> = iovecs
+        .iter_mut()
+        .zip(&mut addrs)
+        .map(|(iov, storage)| msghdr(iov, storage, len))
+        .collect();
+
+    // SAFETY: every header points at an iovec and an address that outlive the call,
+    // and every iovec at a buffer that is borrowed mutably
+    let count = super::wrap_error(|| unsafe {
+        let ret = libc::recvmmsg(
+            fd,
+            msgs.as_mut_ptr(),
+            msgs.len() as libc::c_uint,
+            0,
+            core::ptr::null_mut(),
+        );
+        (ret, ret)
+    })? as usize;
+
+    for (msg, storage) in msgs[..count].iter().zip(&addrs) {
+        received.push((msg.msg_len as usize, to_socket_addr(storage)?));
+    }
+    Ok(count)
+}
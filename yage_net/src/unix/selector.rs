@@ -3,7 +3,9 @@ use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
-use libc::{EPOLLET, EPOLLIN, EPOLLOUT, EPOLLPRI, EPOLLRDHUP};
+use libc::{EPOLLET, EPOLLIN, EPOLLONESHOT, EPOLLOUT, EPOLLPRI, EPOLLRDHUP};
+
+use crate::Trigger;
 
 fn next_id() -> usize {
     static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
@@ -71,9 +73,10 @@ impl Selector {
         fd: RawFd,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: Trigger,
     ) -> io::Result<()> {
         let mut event = libc::epoll_event {
-            events: interest_to_epoll(interests),
+            events: interest_to_epoll(interests, trigger),
             u64: token.0 as _,
             #[cfg(target_os = "redox")]
             _pad: 0,
@@ -90,9 +93,10 @@ impl Selector {
         fd: RawFd,
         token: crate::Token,
         interests: crate::Interest,
+        trigger: Trigger,
     ) -> io::Result<()> {
         let mut event = libc::epoll_event {
-            events: interest_to_epoll(interests),
+            events: interest_to_epoll(interests, trigger),
             u64: token.0 as _,
             #[cfg(target_os = "redox")]
             _pad: 0,
@@ -118,6 +122,95 @@ impl Selector {
     }
 }
 
-fn interest_to_epoll(interests: crate::Interest) -> u32 {
-    todo!()
+fn interest_to_epoll(interests: crate::Interest, trigger: Trigger) -> u32 {
+    let mut kind = match trigger {
+        Trigger::Edge => EPOLLET,
+        Trigger::Level => 0,
+        Trigger::Oneshot => EPOLLONESHOT,
+    };
+
+    if interests.is_readable() {
+        kind |= EPOLLIN;
+    }
+    if interests.is_writable() {
+        kind |= EPOLLOUT;
+    }
+    if interests.is_priority() {
+        kind |= EPOLLPRI;
+    }
+    if interests.is_read_closed() {
+        kind |= EPOLLRDHUP;
+    }
+
+    kind as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Interest, Token};
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    fn ready_count(selector: &Selector) -> usize {
+        let mut events = Vec::with_capacity(8);
+        selector.select(&mut events, Some(Duration::ZERO)).unwrap();
+        events.len()
+    }
+
+    /// a selector with the reading end of a socket pair registered, which has data waiting
+    fn readable(trigger: Trigger) -> (Selector, UnixStream, UnixStream) {
+        let selector = Selector::new().unwrap();
+        let (a, mut b) = UnixStream::pair().unwrap();
+        selector
+            .register(a.as_raw_fd(), Token(1), Interest::READABLE, trigger)
+            .unwrap();
+        b.write_all(&[1]).unwrap();
+        (selector, a, b)
+    }
+
+    #[test]
+    fn interests_are_translated() {
+        let all =
+            Interest::READABLE | Interest::WRITABLE | Interest::PRIORITY | Interest::READ_CLOSED;
+        let events = interest_to_epoll(all, Trigger::Level);
+        assert_eq!(events, (EPOLLIN | EPOLLOUT | EPOLLPRI | EPOLLRDHUP) as u32);
+
+        let writable = all.remove(Interest::READABLE | Interest::PRIORITY | Interest::READ_CLOSED);
+        assert_eq!(writable, Some(Interest::WRITABLE));
+        assert_eq!(Interest::READABLE.remove(Interest::READABLE), None);
+        assert!(all.is_priority() && !Interest::READABLE.is_writable());
+    }
+
+    #[test]
+    fn edge_triggered_is_reported_once() {
+        let (selector, _a, _b) = readable(Trigger::Edge);
+        assert_eq!(ready_count(&selector), 1);
+        assert_eq!(ready_count(&selector), 0);
+    }
+
+    #[test]
+    fn level_triggered_is_reported_while_ready() {
+        let (selector, _a, _b) = readable(Trigger::Level);
+        assert_eq!(ready_count(&selector), 1);
+        assert_eq!(ready_count(&selector), 1);
+    }
+
+    #[test]
+    fn oneshot_is_reported_until_rearmed() {
+        let (selector, a, _b) = readable(Trigger::Oneshot);
+        assert_eq!(ready_count(&selector), 1);
+        assert_eq!(ready_count(&selector), 0);
+
+        selector
+            .reregister(
+                a.as_raw_fd(),
+                Token(1),
+                Interest::READABLE,
+                Trigger::Oneshot,
+            )
+            .unwrap();
+        assert_eq!(ready_count(&selector), 1);
+    }
 }
@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::{self, Read, Write};
 use std::os::fd::{AsRawFd, FromRawFd};
 
-use crate::{Interest, Token};
+use crate::{Interest, Token, Trigger};
 
 use super::selector::Selector;
 
@@ -23,7 +23,12 @@ impl Waker {
 
     pub(crate) fn new(selector: &Selector, token: Token) -> io::Result<Self> {
         let this = Self::new_unregistered()?;
-        selector.register(this.inner.as_raw_fd(), token, Interest::READABLE)?;
+        selector.register(
+            this.inner.as_raw_fd(),
+            token,
+            Interest::READABLE,
+            Trigger::Edge,
+        )?;
         Ok(this)
     }
 
@@ -7,7 +7,10 @@ pub struct IoWaker {
 }
 
 impl IoWaker {
+    /// # Panics
+    /// if `registry` already has a waker
     pub fn new(registry: &Registry, token: Token) -> io::Result<Self> {
+        registry.register_waker();
         unix::waker::Waker::new(&registry.selector, token).map(|wk| Self { inner: wk })
     }
 
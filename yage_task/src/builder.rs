@@ -1,8 +1,17 @@
+use core::future::Future;
+
+use crate::handle::JoinHandle;
 use crate::header::Tag;
+use crate::local::{LocalKey, Locals};
+use crate::task::Task;
+use crate::{Raw, Schedule};
 
 pub struct Builder<M = (), E = ()> {
     pub(crate) metadata: M,
     pub(crate) tag: E,
+    pub(crate) locals: Locals,
+    #[cfg(feature = "registry")]
+    pub(crate) registry: Option<alloc::sync::Arc<crate::registry::Inner>>,
 }
 
 impl Builder {
@@ -10,11 +19,28 @@ impl Builder {
         Self {
             metadata: (),
             tag: (),
+            locals: Locals::new(),
+            #[cfg(feature = "registry")]
+            registry: None,
         }
     }
 
-    pub const fn metadata<M>(self, metadata: M) -> Builder<M> {
-        Builder { metadata, tag: () }
+    /// sets the metadata of the spawned task.
+    /// this clears the registry, as it is tied to the type of the metadata
+    pub fn metadata<M>(self, metadata: M) -> Builder<M> {
+        Builder {
+            metadata,
+            tag: (),
+            locals: self.locals,
+            #[cfg(feature = "registry")]
+            registry: None,
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -23,8 +49,79 @@ impl<M> Builder<M> {
         Builder {
             tag,
             metadata: self.metadata,
+            locals: self.locals,
+            #[cfg(feature = "registry")]
+            registry: self.registry,
         }
     }
 }
 
-impl<M, E> Builder<M, E> {}
+impl<M, E> Builder<M, E> {
+    /// sets the value of a task-local `key` for the spawned task.
+    /// see `task_local!`
+    pub fn local<T: Send + 'static>(mut self, key: &'static LocalKey<T>, value: T) -> Self {
+        self.locals.insert(key, value);
+        self
+    }
+
+    /// tracks the spawned task in `registry` until it is freed
+    #[cfg(feature = "registry")]
+    pub fn registry(mut self, registry: &crate::registry::Registry<M>) -> Self {
+        self.registry = Some(alloc::sync::Arc::clone(&registry.inner));
+        self
+    }
+}
+
+impl<M, E> Builder<M, E>
+where
+    E: Tag,
+{
+    /// allocates a new task, running the future produced by `future`.
+    ///
+    /// `future` is given a reference to the metadata of the task, which lives as long as the future does.
+    /// `schedule` is called with the returned `Task` every time the task is woken,
+    /// and the returned `JoinHandle` can be used to await the output
+    #[track_caller]
+    pub fn spawn<Fun, F, S>(self, future: Fun, schedule: S) -> (Task<M>, JoinHandle<F::Output, M>)
+    where
+        Fun: FnOnce(&M) -> F,
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+        S: Schedule<M> + Send + Sync + 'static,
+        M: Send + Sync + 'static,
+    {
+        // SAFETY: all of the requirements are upheld by the bounds above
+        unsafe { self.spawn_unchecked(future, schedule) }
+    }
+
+    /// the same as `Builder::spawn`, without any `Send` or `'static` bounds.
+    ///
+    /// # Safety
+    /// - if `F` is not `Send`, the returned `Task` (and its wakers) must only be used on the thread it was created on
+    /// - if `F` or `F::Output` is not `'static`, the task must be finished or dropped before anything they borrow
+    /// - if `S` is not `Send + Sync`, every waker of the task must be used and dropped on the thread it was created on
+    #[track_caller]
+    pub unsafe fn spawn_unchecked<'a, Fun, F, S>(
+        self,
+        future: Fun,
+        schedule: S,
+    ) -> (Task<M>, JoinHandle<F::Output, M>)
+    where
+        Fun: FnOnce(&'a M) -> F,
+        F: Future + 'a,
+        S: Schedule<M>,
+        M: 'a,
+    {
+        let ptr = Raw::<F, F::Output, S, M, E>::allocate(future, schedule, self);
+
+        let task = Task {
+            ptr,
+            _marker: core::marker::PhantomData,
+        };
+        let handle = JoinHandle {
+            ptr,
+            _marker: core::marker::PhantomData,
+        };
+        (task, handle)
+    }
+}
@@ -0,0 +1,122 @@
+//! cooperative scheduling.
+//!
+//! every time a task is polled, it is given a budget of `DEFAULT_BUDGET` operations.
+//! leaf futures (I/O, timers, join handles) call `poll_proceed` before doing any work,
+//! and once the budget runs out they return `Pending` and wake the task right away.
+//! this forces a future that loops over something that is always ready to yield back to the executor
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// the number of operations a task can do in a single poll
+pub const DEFAULT_BUDGET: u8 = 128;
+
+/// consumes one unit of budget.
+///
+/// returns `Pending` (and wakes the task) if the budget has run out.
+/// outside of a task, there is no budget, so this always returns `Ready`
+pub fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+    match current::get() {
+        None => Poll::Ready(()),
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(n) => {
+            current::set(Some(n - 1));
+            Poll::Ready(())
+        }
+    }
+}
+
+/// returns `false` if the current task has used up its budget for this poll
+pub fn has_budget_remaining() -> bool {
+    current::get() != Some(0)
+}
+
+/// consumes one unit of budget, yielding if it has run out.
+///
+/// useful for loops that don't go through any leaf futures
+pub fn consume_budget() -> ConsumeBudget {
+    ConsumeBudget { _priv: () }
+}
+
+/// the future returned by `consume_budget`
+#[must_use = "futures do nothing unless polled"]
+pub struct ConsumeBudget {
+    _priv: (),
+}
+
+impl Future for ConsumeBudget {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        poll_proceed(cx)
+    }
+}
+
+/// gives the current thread a fresh budget while a task is being polled,
+/// restoring the previous budget when dropped
+pub(crate) struct Reset {
+    prev: Option<u8>,
+}
+
+impl Reset {
+    pub(crate) fn new(budget: u8) -> Self {
+        let prev = current::get();
+        current::set(Some(budget));
+        Self { prev }
+    }
+}
+
+impl Drop for Reset {
+    fn drop(&mut self) {
+        current::set(self.prev);
+    }
+}
+
+#[cfg(feature = "std")]
+mod current {
+    use core::cell::Cell;
+
+    #[cfg(not(loom))]
+    std::thread_local! {
+        static BUDGET: Cell<Option<u8>> = const { Cell::new(None) };
+    }
+
+    // loom runs every modelled thread on the same OS thread
+    #[cfg(loom)]
+    loom::thread_local! {
+        static BUDGET: Cell<Option<u8>> = Cell::new(None);
+    }
+
+    pub(super) fn get() -> Option<u8> {
+        BUDGET.with(Cell::get)
+    }
+
+    pub(super) fn set(budget: Option<u8>) {
+        BUDGET.with(|b| b.set(budget))
+    }
+}
+
+/// like the task-local values, this assumes tasks are only polled from one core without `std`
+#[cfg(not(feature = "std"))]
+mod current {
+    use core::sync::atomic::{AtomicU16, Ordering};
+
+    const UNCONSTRAINED: u16 = u16::MAX;
+
+    static BUDGET: AtomicU16 = AtomicU16::new(UNCONSTRAINED);
+
+    pub(super) fn get() -> Option<u8> {
+        match BUDGET.load(Ordering::Relaxed) {
+            UNCONSTRAINED => None,
+            n => Some(n as u8),
+        }
+    }
+
+    pub(super) fn set(budget: Option<u8>) {
+        BUDGET.store(budget.map_or(UNCONSTRAINED, u16::from), Ordering::Relaxed)
+    }
+}
@@ -33,4 +33,8 @@ impl crate::state::State {
     pub const fn has_valid_handle(&self) -> bool {
         self.has_flag_set(HANDLE_HERE)
     }
+
+    pub const fn has_awaiter(&self) -> bool {
+        self.has_flag_set(WAKER_IN_HERE)
+    }
 }
@@ -0,0 +1,373 @@
+use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+
+use crate::header::{Header, Tag};
+use crate::state::State;
+use crate::{Panic, flags};
+
+/// a handle to a spawned task, that can be used to await its output.
+///
+/// there is only ever one `JoinHandle` per task, and the output can only be taken once.
+/// dropping the handle detaches the task: it keeps running, but its output is dropped when it completes.
+/// use `JoinHandle::cancel` to stop it instead
+pub struct JoinHandle<T, M = ()> {
+    pub(crate) ptr: NonNull<()>,
+    pub(crate) _marker: PhantomData<(T, M)>,
+}
+
+// SAFETY: the handle only ever hands out `T` by value, and `M` by shared reference
+unsafe impl<T: Send, M: Send + Sync> Send for JoinHandle<T, M> {}
+unsafe impl<T, M: Send + Sync> Sync for JoinHandle<T, M> {}
+
+impl<T, M> Unpin for JoinHandle<T, M> {}
+
+impl<T, M> JoinHandle<T, M> {
+    fn header(&self) -> *const Header<M> {
+        self.ptr.as_ptr() as *const Header<M>
+    }
+
+    /// a snapshot of the current state of this task
+    pub fn state(&self) -> State {
+        unsafe { (*self.header()).state.load(Ordering::Acquire) }
+    }
+
+    /// the metadata this task was spawned with
+    pub fn metadata(&self) -> &M {
+        unsafe { &(*self.header()).metadata }
+    }
+
+    /// the current tag of this task.
+    ///
+    /// `E` should be the same type the task was spawned with, see `Builder::tag`
+    pub fn tag<E: Tag>(&self) -> E {
+        unsafe { (*self.header()).tag() }
+    }
+
+    /// atomically replaces the tag of this task with the result of `f`, returning the old tag.
+    /// `f` may be called more than once if the state of the task changes concurrently
+    pub fn update_tag<E, F>(&self, f: F) -> E
+    where
+        E: Tag,
+        F: FnMut(E) -> E,
+    {
+        unsafe { (*self.header()).update_tag(f) }
+    }
+
+    /// returns `true` if the task has completed, and its output is ready to be taken
+    pub fn is_finished(&self) -> bool {
+        let state = self.state();
+        state.is_completed() || state.is_closed()
+    }
+
+    /// blocks the current thread until the task completes.
+    ///
+    /// returns `None` if the task was closed before it could complete.
+    /// if the task panicked, the panic is resumed on this thread.
+    /// without `std`, this spins instead of parking the thread
+    pub fn join(mut self) -> Option<T> {
+        self.block().map(unwrap_output)
+    }
+
+    /// the same as `JoinHandle::join`, but a panic in the task is returned as an error instead of being resumed
+    pub fn try_join(mut self) -> Result<T, JoinError> {
+        match self.block() {
+            Some(out) => out.map_err(JoinError::Panicked),
+            None => Err(JoinError::Cancelled),
+        }
+    }
+
+    /// polls for the output of the task, without resuming panics
+    pub fn poll_join(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, JoinError>> {
+        self.poll_task(cx).map(|out| match out {
+            Some(out) => out.map_err(JoinError::Panicked),
+            None => Err(JoinError::Cancelled),
+        })
+    }
+
+    fn block(&mut self) -> Option<Result<T, Panic>> {
+        let blocker = Arc::new(Blocker {
+            notified: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            thread: std::thread::current(),
+        });
+        let waker = Waker::from(blocker.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if let Poll::Ready(out) = self.poll_task(&mut cx) {
+                return out;
+            }
+
+            while !blocker.notified.swap(false, Ordering::Acquire) {
+                #[cfg(feature = "std")]
+                std::thread::park();
+                #[cfg(not(feature = "std"))]
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    /// cancels the task.
+    ///
+    /// the future is dropped the next time the task is scheduled (or right away, if it isn't running).
+    /// if the task had already completed, its output is returned
+    pub fn cancel(self) -> Option<T> {
+        let mut this = ManuallyDrop::new(self);
+        this.set_cancelled();
+        this.set_detached().map(unwrap_output)
+    }
+
+    /// lets the task run to completion without a handle.
+    /// the output is dropped once it completes
+    ///
+    /// this is the same as dropping the `JoinHandle`
+    pub fn detach(self) {
+        let mut this = ManuallyDrop::new(self);
+        let _ = this.set_detached();
+    }
+
+    /// closes the task, so its future is dropped at the next scheduling point
+    pub(crate) fn set_cancelled(&mut self) {
+        unsafe { (*self.header()).cancel() }
+    }
+
+    pub(crate) fn poll_task(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, Panic>>> {
+        let ptr = self.ptr.as_ptr();
+        let header = self.header();
+
+        unsafe {
+            let mut state = (*header).state.load(Ordering::Acquire);
+
+            loop {
+                if state.is_closed() {
+                    // the future still exists, so wait until whoever holds it drops it
+                    if state.is_scheduled() || state.is_running() {
+                        (*header).register(cx.waker());
+
+                        state = (*header).state.load(Ordering::Acquire);
+                        if state.is_scheduled() || state.is_running() {
+                            return Poll::Pending;
+                        }
+                    }
+
+                    (*header).notify(Some(cx.waker()));
+                    return Poll::Ready(None);
+                }
+
+                if !state.is_completed() {
+                    (*header).register(cx.waker());
+
+                    // the task could have completed while we were registering
+                    state = (*header).state.load(Ordering::Acquire);
+                    if state.is_closed() {
+                        continue;
+                    }
+                    if !state.is_completed() {
+                        return Poll::Pending;
+                    }
+                }
+
+                // closing a completed task gives us ownership of the output
+                match (*header).state.compare_exchange_weak(
+                    state,
+                    state.set_flag(flags::CLOSED | flags::TAKEN),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        if state.has_awaiter() {
+                            (*header).notify(Some(cx.waker()));
+                        }
+
+                        let output = ((*header).vtable.get_output)(ptr) as *mut Result<T, Panic>;
+                        return Poll::Ready(Some(output.read()));
+                    }
+                    Err(s) => state = s,
+                }
+            }
+        }
+    }
+
+    /// gives up ownership of the task, returning the output if it had completed but wasn't taken yet
+    pub(crate) fn set_detached(&mut self) -> Option<Result<T, Panic>> {
+        let ptr = self.ptr.as_ptr();
+        let header = self.header();
+
+        unsafe {
+            let mut output = None;
+            let mut state = (*header).state.load(Ordering::Acquire);
+
+            loop {
+                if state.is_completed() && !state.is_closed() {
+                    // close it first, so the output belongs to us
+                    match (*header).state.compare_exchange_weak(
+                        state,
+                        state.set_flag(flags::CLOSED | flags::TAKEN),
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    ) {
+                        Ok(_) => {
+                            let out = ((*header).vtable.get_output)(ptr) as *mut Result<T, Panic>;
+                            output = Some(out.read());
+                            state = state.set_flag(flags::CLOSED | flags::TAKEN);
+                        }
+                        Err(s) => state = s,
+                    }
+                } else {
+                    // if this is the last reference and the future is still alive,
+                    // schedule it one last time, so the executor drops it
+                    let last = state.reference_count == 0;
+                    let new = if last && !state.is_closed() {
+                        State {
+                            reference_count: 1,
+                            flags: flags::SCHEDULED | flags::CLOSED,
+                            tag: state.tag,
+                        }
+                    } else {
+                        state.clear_flag(flags::HANDLE_HERE)
+                    };
+
+                    match (*header).state.compare_exchange_weak(
+                        state,
+                        new,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    ) {
+                        Ok(_) => {
+                            if last {
+                                if state.is_closed() {
+                                    ((*header).vtable.destroy)(ptr);
+                                } else {
+                                    ((*header).vtable.schedule)(ptr);
+                                }
+                            }
+                            break;
+                        }
+                        Err(s) => state = s,
+                    }
+                }
+            }
+
+            output
+        }
+    }
+}
+
+impl<T, M> Future for JoinHandle<T, M> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        core::task::ready!(crate::coop::poll_proceed(cx));
+
+        let taken = self.state().has_been_taken();
+        match self.poll_task(cx) {
+            Poll::Ready(Some(out)) => Poll::Ready(unwrap_output(out)),
+            Poll::Ready(None) if taken => panic!("`JoinHandle` polled after completion"),
+            Poll::Ready(None) => panic!("task was closed before it could complete"),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T, M> Drop for JoinHandle<T, M> {
+    fn drop(&mut self) {
+        let _ = self.set_detached();
+    }
+}
+
+impl<T, M> core::fmt::Debug for JoinHandle<T, M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("ptr", &self.ptr)
+            .field("state", &self.state())
+            .finish()
+    }
+}
+
+/// the reason a task didn't produce an output
+pub enum JoinError {
+    /// the task was closed before it could complete
+    Cancelled,
+    /// the future panicked while being polled.
+    /// without `std`, panics abort instead
+    Panicked(Panic),
+}
+
+impl JoinError {
+    pub const fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Cancelled)
+    }
+
+    pub const fn is_panic(&self) -> bool {
+        matches!(self, Self::Panicked(_))
+    }
+
+    /// returns the panic payload, if the task panicked
+    pub fn into_panic(self) -> Option<Panic> {
+        match self {
+            Self::Panicked(panic) => Some(panic),
+            Self::Cancelled => None,
+        }
+    }
+}
+
+impl core::fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Cancelled => f.write_str("Cancelled"),
+            Self::Panicked(_) => f.write_str("Panicked(..)"),
+        }
+    }
+}
+
+impl core::fmt::Display for JoinError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Cancelled => f.write_str("task was cancelled"),
+            Self::Panicked(_) => f.write_str("task panicked"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for JoinError {}
+
+#[cfg(feature = "std")]
+fn unwrap_output<T>(out: Result<T, Panic>) -> T {
+    out.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+#[cfg(not(feature = "std"))]
+fn unwrap_output<T>(out: Result<T, Panic>) -> T {
+    match out {
+        Ok(out) => out,
+        Err(never) => match never {},
+    }
+}
+
+/// the waker used by `JoinHandle::join`
+struct Blocker {
+    notified: AtomicBool,
+    #[cfg(feature = "std")]
+    thread: std::thread::Thread,
+}
+
+impl Wake for Blocker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::Release);
+        #[cfg(feature = "std")]
+        self.thread.unpark();
+    }
+}
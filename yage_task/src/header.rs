@@ -1,18 +1,28 @@
+use crate::local::Locals;
 use crate::state::{AtomicState, State};
 use crate::{TaskVTable, flags};
 
+use crate::utils::abort_on_panic;
+
 use core::cell::UnsafeCell;
 use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
 use core::task::Waker;
 
 /// the header of a `Task`
 ///
 /// this carries various bookkeeping fields, like the state and the actual vtable
 /// this also carries 2 different fields for metadata, including a 16-bit `Tag`, as well as a `Metadata` field
+///
+/// `metadata` is kept last, so every other field is at the same offset no matter what `Metadata` is
+#[repr(C)]
 pub(crate) struct Header<Metadata, T: Tag = ()> {
     pub(crate) state: AtomicState,
     pub(crate) awaiter: UnsafeCell<Option<Waker>>,
     pub(crate) vtable: &'static TaskVTable,
+    pub(crate) locals: Locals,
+    #[cfg(feature = "registry")]
+    pub(crate) entry: Option<crate::registry::Entry>,
     pub(crate) metadata: Metadata,
     pub(crate) _marker: PhantomData<T>,
 }
@@ -26,6 +36,8 @@ where
         metadata: Meta,
         vtable: &'static TaskVTable,
         tag: T,
+        locals: Locals,
+        #[cfg(feature = "registry")] entry: Option<crate::registry::Entry>,
         ptr: *const (),
     ) {
         let ptr = ptr as *const Header<Meta, T> as *mut Header<Meta, T>;
@@ -38,6 +50,9 @@ where
                 }),
                 awaiter: UnsafeCell::new(None),
                 vtable,
+                locals,
+                #[cfg(feature = "registry")]
+                entry,
                 metadata,
                 _marker: PhantomData,
             });
@@ -45,10 +60,191 @@ where
     }
 }
 
+impl<Meta, T> Header<Meta, T>
+where
+    T: Tag,
+{
+    /// closes the task, so its future is dropped at the next scheduling point
+    ///
+    /// SAFETY: `self` must be the header of a live task
+    pub(crate) unsafe fn cancel(&self) {
+        let ptr = self as *const Self as *const ();
+        let mut state = self.state.load(Ordering::Acquire);
+
+        loop {
+            // too late to cancel
+            if state.is_completed() || state.is_closed() {
+                break;
+            }
+
+            // if nobody is going to run the task, we have to schedule it ourselves,
+            // so the new `Task` needs its own reference
+            let idle = !state.is_scheduled() && !state.is_running();
+            let new = if idle {
+                state
+                    .set_flag(flags::SCHEDULED | flags::CLOSED)
+                    .increment_reference_count()
+            } else {
+                state.set_flag(flags::CLOSED)
+            };
+
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    if idle {
+                        unsafe { (self.vtable.schedule)(ptr) };
+                    }
+
+                    if state.has_awaiter() {
+                        self.notify(None);
+                    }
+                    break;
+                }
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// wakes the awaiter, if one is registered.
+    ///
+    /// if `current` is the awaiter, it isn't woken, as the caller is already running
+    pub(crate) fn notify(&self, current: Option<&Waker>) {
+        if let Some(w) = self.take(current) {
+            abort_on_panic(|| w.wake());
+        }
+    }
+
+    /// takes the awaiter out, if one is registered, and nobody else is touching it.
+    ///
+    /// returns `None` if the awaiter is the same as `current`
+    pub(crate) fn take(&self, current: Option<&Waker>) -> Option<Waker> {
+        let state = self.state.update(Ordering::AcqRel, Ordering::Acquire, |s| {
+            s.set_flag(flags::WAKER_NOTIFYING)
+        });
+
+        // if someone is already registering or notifying, they will take care of it
+        if state.has_flag_set(flags::WAKER_NOTIFYING | flags::WAKER_REGISTERING) {
+            return None;
+        }
+
+        // SAFETY: the `WAKER_NOTIFYING` bit gives us exclusive access to the awaiter
+        let waker = unsafe { (*self.awaiter.get()).take() };
+
+        self.state
+            .update(Ordering::Release, Ordering::Acquire, |s| {
+                s.clear_flag(flags::WAKER_NOTIFYING | flags::WAKER_IN_HERE)
+            });
+
+        match (waker, current) {
+            (Some(w), Some(c)) if w.will_wake(c) => {
+                abort_on_panic(|| drop(w));
+                None
+            }
+            (waker, _) => waker,
+        }
+    }
+
+    /// registers `waker` as the awaiter of this task, replacing the old one
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut state = self.state.load(Ordering::Acquire);
+
+        loop {
+            // there can only be one `JoinHandle`, and registering needs `&mut` to it
+            debug_assert!(!state.has_flag_set(flags::WAKER_REGISTERING));
+
+            // we are being notified right now, so just wake instead of registering
+            if state.has_flag_set(flags::WAKER_NOTIFYING) {
+                abort_on_panic(|| waker.wake_by_ref());
+                return;
+            }
+
+            match self.state.compare_exchange_weak(
+                state,
+                state.set_flag(flags::WAKER_REGISTERING),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    state = state.set_flag(flags::WAKER_REGISTERING);
+                    break;
+                }
+                Err(s) => state = s,
+            }
+        }
+
+        // SAFETY: the `WAKER_REGISTERING` bit gives us exclusive access to the awaiter
+        unsafe { abort_on_panic(|| *self.awaiter.get() = Some(waker.clone())) };
+
+        // set if a notification comes in while we are registering
+        let mut notified = None;
+
+        loop {
+            if state.has_flag_set(flags::WAKER_NOTIFYING)
+                && let Some(w) = unsafe { (*self.awaiter.get()).take() }
+            {
+                notified = Some(w);
+            }
+
+            let new = state.clear_flag(flags::WAKER_NOTIFYING | flags::WAKER_REGISTERING);
+            let new = if notified.is_none() {
+                new.set_flag(flags::WAKER_IN_HERE)
+            } else {
+                new.clear_flag(flags::WAKER_IN_HERE)
+            };
+
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(s) => state = s,
+            }
+        }
+
+        if let Some(w) = notified {
+            abort_on_panic(|| w.wake());
+        }
+    }
+}
+
+impl<Meta, T> Header<Meta, T>
+where
+    T: Tag,
+{
+    pub(crate) fn tag<E: Tag>(&self) -> E {
+        E::from_u16(self.state.load(Ordering::Acquire).tag)
+    }
+
+    /// atomically replaces the tag with the result of `f`, returning the old tag.
+    /// `f` may be called more than once, if the state changes underneath it
+    pub(crate) fn update_tag<E, F>(&self, mut f: F) -> E
+    where
+        E: Tag,
+        F: FnMut(E) -> E,
+    {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let mut new = state;
+            new.tag = f(E::from_u16(state.tag)).into_u16();
+
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(old) => return E::from_u16(old.tag),
+                Err(s) => state = s,
+            }
+        }
+    }
+}
+
 /// the main trait for "tagging" a task.
 /// this can be used to atomically indicate certain states or extra metadata
 /// the only requirement is that it can fit into a `u16`
 /// primitives with less than a 16-bit size implement this trait.
+/// fieldless enums with a `#[repr(u8)]`, `#[repr(u16)]` (or signed equivalents) can implement it with `impl_tag!`
 pub trait Tag {
     fn from_u16(val: u16) -> Self;
 
@@ -74,11 +270,46 @@ macro_rules! impl_tag_for_prims {
 impl_tag_for_prims!(u8 u16 i8 i16);
 
 impl Tag for () {
-    fn from_u16(_: u16) -> Self {
-        ()
-    }
+    fn from_u16(_: u16) -> Self {}
 
     fn into_u16(self) -> u16 {
         0
     }
 }
+
+/// implements `Tag` for a fieldless enum, using its `#[repr]` as the conversion.
+///
+/// converting a `u16` that doesn't match any of the listed variants panics.
+///
+/// ```
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// #[repr(u8)]
+/// enum Priority {
+///     Low,
+///     High = 4,
+/// }
+///
+/// yage_task::impl_tag!(Priority as u8 { Low, High });
+///
+/// use yage_task::Tag;
+/// assert_eq!(Priority::from_u16(Priority::High.into_u16()), Priority::High);
+/// ```
+#[macro_export]
+macro_rules! impl_tag {
+    ($ty:ty as $repr:ty { $($variant:ident),+ $(,)? }) => {
+        impl $crate::Tag for $ty {
+            fn from_u16(val: u16) -> Self {
+                $(
+                    if val == <$ty>::$variant as $repr as u16 {
+                        return <$ty>::$variant;
+                    }
+                )+
+                panic!(concat!("invalid tag for `", stringify!($ty), "`: {}"), val)
+            }
+
+            fn into_u16(self) -> u16 {
+                self as $repr as u16
+            }
+        }
+    };
+}
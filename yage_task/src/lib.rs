@@ -2,8 +2,9 @@
 
 use core::marker::PhantomData;
 use core::mem;
+use core::pin::Pin;
 use core::sync::atomic::Ordering;
-use core::task::{RawWaker, Waker};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 use core::{future::Future, ptr::NonNull};
 
 #[cfg(not(feature = "std"))]
@@ -24,14 +25,25 @@ const fn max(lhs: usize, rhs: usize) -> usize {
 }
 
 pub mod builder;
+pub mod coop;
+pub mod handle;
+pub mod local;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod scope;
 pub mod task;
 
 mod flags;
 mod header;
 mod layout;
 mod state;
+mod sync;
 mod utils;
 
+#[cfg(all(test, loom))]
+mod loom_tests;
+
 mod sealed {
     use crate::task::Task;
 
@@ -53,15 +65,20 @@ where
     }
 }
 
-use header::{Header, Tag};
+pub use header::Tag;
+pub use state::State;
+
+use header::Header;
 use layout::ConstLayout;
 use task::Task;
 
+/// the payload of a panic caught while polling a task
 #[cfg(feature = "std")]
-type Panic = alloc::boxed::Box<dyn std::any::Any + Send + 'static>;
+pub type Panic = alloc::boxed::Box<dyn std::any::Any + Send + 'static>;
 
+/// without `std`, panics can't be caught, so this can never be constructed
 #[cfg(not(feature = "std"))]
-type Panic = core::convert::Infallible;
+pub type Panic = core::convert::Infallible;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) enum ReferenceKind {
@@ -134,6 +151,7 @@ where
 {
     const TASK_LAYOUT: TaskLayout = Self::eval_task_layout();
 
+    #[track_caller]
     fn allocate<'a, Gen>(future: Gen, schedule: S, builder: builder::Builder<M, E>) -> NonNull<()>
     where
         Gen: FnOnce(&'a M) -> F,
@@ -148,7 +166,18 @@ where
                 Some(ptr) => ptr,
             };
 
-            let builder::Builder { metadata, tag } = builder;
+            let builder::Builder {
+                metadata,
+                tag,
+                locals,
+                #[cfg(feature = "registry")]
+                registry,
+            } = builder;
+            #[cfg(feature = "registry")]
+            let entry = {
+                let location = core::panic::Location::caller();
+                registry.map(|registry| registry::Entry::new(registry, location))
+            };
             Header::new_in_place(
                 metadata,
                 &TaskVTable {
@@ -162,6 +191,9 @@ where
                     task_layout: &Self::TASK_LAYOUT,
                 },
                 tag,
+                locals,
+                #[cfg(feature = "registry")]
+                entry,
                 ptr.as_ptr(),
             );
 
@@ -173,6 +205,11 @@ where
 
             raw.future.write(future);
 
+            #[cfg(feature = "registry")]
+            if let Some(entry) = &(*raw.header).entry {
+                entry.insert(ptr);
+            }
+
             ptr
         }
     }
@@ -190,9 +227,18 @@ where
         }
     }
 
+    const RAW_WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(
+        Self::clone_waker,
+        Self::wake,
+        Self::wake_by_ref,
+        Self::drop_waker,
+    );
+
     unsafe fn schedule(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
 
+        // if the schedule function captures anything, hold an extra reference while it runs,
+        // so the task can't be freed out from under it
         let _waker;
         if mem::size_of::<S>() > 0 {
             _waker = unsafe { Waker::from_raw(Self::clone_waker(ptr)) }
@@ -208,7 +254,9 @@ where
     }
 
     unsafe fn wake(ptr: *const ()) {
-        if core::mem::size_of::<S>() > 0 {
+        // with a non-zero sized scheduler, `schedule` has to clone a waker anyway,
+        // so it's cheaper to just go through `wake_by_ref`
+        if mem::size_of::<S>() > 0 {
             unsafe {
                 Self::wake_by_ref(ptr);
                 Self::drop_waker(ptr);
@@ -227,6 +275,7 @@ where
             }
 
             if state.is_scheduled() {
+                // already scheduled, just publish our view of memory to whoever runs it next
                 match unsafe {
                     (*raw.header).state.compare_exchange_weak(
                         state,
@@ -235,16 +284,119 @@ where
                         Ordering::Acquire,
                     )
                 } {
-                    Ok(_) => {}
+                    Ok(_) => {
+                        unsafe { Self::drop_waker(ptr) };
+                        return;
+                    }
+                    Err(s) => state = s,
+                }
+            } else {
+                match unsafe {
+                    (*raw.header).state.compare_exchange_weak(
+                        state,
+                        state.set_flag(flags::SCHEDULED),
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                } {
+                    Ok(_) => {
+                        if state.is_running() {
+                            // `run` will reschedule it once the poll finishes
+                            unsafe { Self::drop_waker(ptr) };
+                        } else {
+                            // our reference is handed over to the new `Task`
+                            unsafe { Self::schedule(ptr) };
+                        }
+                        return;
+                    }
                     Err(s) => state = s,
                 }
             }
         }
     }
 
-    unsafe fn wake_by_ref(ptr: *const ()) {}
+    unsafe fn wake_by_ref(ptr: *const ()) {
+        let raw = Self::from_ptr(ptr);
 
-    unsafe fn drop_waker(ptr: *const ()) {}
+        let mut state = unsafe { (*raw.header).state.load(Ordering::Acquire) };
+
+        loop {
+            if state.is_completed() || state.is_closed() {
+                return;
+            }
+
+            if state.is_scheduled() {
+                match unsafe {
+                    (*raw.header).state.compare_exchange_weak(
+                        state,
+                        state,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                } {
+                    Ok(_) => return,
+                    Err(s) => state = s,
+                }
+            } else {
+                // if it isn't running, we schedule it right away, and the new `Task` needs its own reference
+                let new = if state.is_running() {
+                    state.set_flag(flags::SCHEDULED)
+                } else {
+                    state.set_flag(flags::SCHEDULED).increment_reference_count()
+                };
+
+                match unsafe {
+                    (*raw.header).state.compare_exchange_weak(
+                        state,
+                        new,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                } {
+                    Ok(_) => {
+                        if !state.is_running() {
+                            unsafe { Self::schedule(ptr) };
+                        }
+                        return;
+                    }
+                    Err(s) => state = s,
+                }
+            }
+        }
+    }
+
+    unsafe fn drop_waker(ptr: *const ()) {
+        let raw = Self::from_ptr(ptr);
+
+        let new = unsafe {
+            (*raw.header)
+                .state
+                .update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                    s.decrement_reference_count()
+                })
+                .decrement_reference_count()
+        };
+
+        if new.reference_count == 0 && !new.has_valid_handle() {
+            if new.is_completed() || new.is_closed() {
+                unsafe { Self::destroy(ptr as *mut ()) };
+            } else {
+                // nobody can observe this task anymore, but the future is still alive.
+                // close it and schedule it one last time, so the executor drops the future
+                unsafe {
+                    (*raw.header).state.store(
+                        State {
+                            reference_count: 1,
+                            flags: flags::SCHEDULED | flags::CLOSED,
+                            tag: new.tag,
+                        },
+                        Ordering::Release,
+                    );
+                    Self::schedule(ptr);
+                }
+            }
+        }
+    }
 
     unsafe fn drop_future(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
@@ -256,18 +408,972 @@ where
 
     unsafe fn get_output(ptr: *mut ()) -> *mut () {
         let raw = Self::from_ptr(ptr);
-        raw.future as *mut ()
+        raw.result as *mut ()
+    }
+
+    unsafe fn drop_reference(ptr: *const ()) {
+        let raw = Self::from_ptr(ptr);
+
+        let new = unsafe {
+            (*raw.header)
+                .state
+                .update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                    s.decrement_reference_count()
+                })
+                .decrement_reference_count()
+        };
+
+        if new.reference_count == 0 && !new.has_valid_handle() {
+            unsafe { Self::destroy(ptr as *mut ()) };
+        }
     }
 
-    unsafe fn drop_reference(ptr: *const ()) {}
+    unsafe fn destroy(ptr: *mut ()) {
+        let raw = Self::from_ptr(ptr);
 
-    unsafe fn destroy(ptr: *mut ()) {}
+        // nothing can look at the task through the registry once it's gone from there
+        #[cfg(feature = "registry")]
+        if let Some(entry) = unsafe { &(*raw.header).entry } {
+            entry.remove();
+        }
+
+        // destructors can panic, and we can't unwind with a half-freed task
+        utils::abort_on_panic(|| unsafe {
+            (raw.header as *mut Header<M, E>).drop_in_place();
+            (raw.schedule as *mut S).drop_in_place();
+        });
+
+        unsafe { alloc::alloc::dealloc(ptr as *mut u8, Self::TASK_LAYOUT.layout) }
+    }
 
     unsafe fn run(ptr: *mut ()) -> bool {
+        let raw = Self::from_ptr(ptr);
+
+        // the `Task` we were called through holds a reference, so this waker doesn't need its own
+        let waker = mem::ManuallyDrop::new(unsafe {
+            Waker::from_raw(RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE))
+        });
+        let cx = &mut Context::from_waker(&waker);
+
+        let mut state = unsafe { (*raw.header).state.load(Ordering::Acquire) };
+
+        loop {
+            if state.is_closed() {
+                unsafe {
+                    Self::drop_future(ptr);
+                    let state =
+                        (*raw.header)
+                            .state
+                            .update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                                s.clear_flag(flags::SCHEDULED)
+                            });
+
+                    let awaiter = if state.has_awaiter() {
+                        (*raw.header).take(None)
+                    } else {
+                        None
+                    };
+
+                    Self::drop_reference(ptr);
+
+                    if let Some(w) = awaiter {
+                        utils::abort_on_panic(|| w.wake());
+                    }
+                }
+                return false;
+            }
+
+            let new = state.clear_flag(flags::SCHEDULED).set_flag(flags::RUNNING);
+            match unsafe {
+                (*raw.header).state.compare_exchange_weak(
+                    state,
+                    new,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+            } {
+                Ok(_) => {
+                    state = new;
+                    break;
+                }
+                Err(s) => state = s,
+            }
+        }
+
+        #[cfg(feature = "registry")]
+        if let Some(entry) = unsafe { &(*raw.header).entry } {
+            entry.polls.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let poll = {
+            let _enter = local::Enter::new(unsafe { &raw const (*raw.header).locals });
+            let _budget = coop::Reset::new(coop::DEFAULT_BUDGET);
+            unsafe { Self::poll_future(raw, cx) }
+        };
+
+        match poll {
+            Poll::Ready(out) => unsafe {
+                Self::drop_future(ptr);
+                raw.result.write(out);
+
+                let prev = (*raw.header)
+                    .state
+                    .update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                        let s = s
+                            .clear_flag(flags::RUNNING | flags::SCHEDULED)
+                            .set_flag(flags::COMPLETED);
+                        if s.has_valid_handle() {
+                            s
+                        } else {
+                            s.set_flag(flags::CLOSED)
+                        }
+                    });
+
+                // if nobody is left to take the output, it gets dropped here
+                let output = if !prev.has_valid_handle() || prev.is_closed() {
+                    Some(raw.result.read())
+                } else {
+                    None
+                };
+
+                let awaiter = if prev.has_awaiter() {
+                    (*raw.header).take(None)
+                } else {
+                    None
+                };
+
+                Self::drop_reference(ptr);
+                drop(output);
+
+                if let Some(w) = awaiter {
+                    utils::abort_on_panic(|| w.wake());
+                }
+            },
+            Poll::Pending => {
+                let mut future_dropped = false;
+
+                loop {
+                    // if the task was closed while running, the future is ours to drop
+                    if state.is_closed() && !future_dropped {
+                        unsafe { Self::drop_future(ptr) };
+                        future_dropped = true;
+                    }
+
+                    let new = if state.is_closed() {
+                        state.clear_flag(flags::RUNNING | flags::SCHEDULED)
+                    } else {
+                        state.clear_flag(flags::RUNNING)
+                    };
+
+                    match unsafe {
+                        (*raw.header).state.compare_exchange_weak(
+                            state,
+                            new,
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                        )
+                    } {
+                        Ok(state) => {
+                            if state.is_closed() {
+                                let awaiter = if state.has_awaiter() {
+                                    unsafe { (*raw.header).take(None) }
+                                } else {
+                                    None
+                                };
+
+                                unsafe { Self::drop_reference(ptr) };
+
+                                if let Some(w) = awaiter {
+                                    utils::abort_on_panic(|| w.wake());
+                                }
+                            } else if state.is_scheduled() {
+                                // we were woken while running, the waker left rescheduling to us,
+                                // so our reference goes to the new `Task`
+                                unsafe { Self::schedule(ptr) };
+                                return true;
+                            } else {
+                                unsafe { Self::drop_reference(ptr) };
+                            }
+                            break;
+                        }
+                        Err(s) => state = s,
+                    }
+                }
+            }
+        }
+
         false
     }
 
+    /// polls the future, catching a panic and storing it as the output
+    #[cfg(feature = "std")]
+    unsafe fn poll_future(raw: Self, cx: &mut Context<'_>) -> Poll<Result<T, Panic>> {
+        let future = unsafe { Pin::new_unchecked(&mut *raw.future) };
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Ready(out)) => Poll::Ready(Ok(out)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+
+    /// polls the future. panics can't be caught without `std`,
+    /// so if it does, the task is closed while the panic unwinds
+    #[cfg(not(feature = "std"))]
+    unsafe fn poll_future(raw: Self, cx: &mut Context<'_>) -> Poll<Result<T, Panic>> {
+        let guard = PollGuard(raw);
+        let poll = unsafe { Pin::new_unchecked(&mut *raw.future).poll(cx) };
+        mem::forget(guard);
+        poll.map(Ok)
+    }
+
     unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
-        todo!()
+        let raw = Self::from_ptr(ptr);
+
+        let prev = unsafe {
+            (*raw.header)
+                .state
+                .update(Ordering::Relaxed, Ordering::Relaxed, |s| {
+                    if s.reference_count == u32::MAX {
+                        utils::abort();
+                    }
+                    s.increment_reference_count()
+                })
+        };
+        debug_assert!(prev.reference_count != 0);
+
+        RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE)
+    }
+}
+
+/// closes the task if polling its future panics
+#[cfg(not(feature = "std"))]
+struct PollGuard<F, T, S, M, E>(Raw<F, T, S, M, E>)
+where
+    F: Future<Output = T>,
+    S: Schedule<M>,
+    E: Tag;
+
+#[cfg(not(feature = "std"))]
+impl<F, T, S, M, E> Drop for PollGuard<F, T, S, M, E>
+where
+    F: Future<Output = T>,
+    S: Schedule<M>,
+    E: Tag,
+{
+    fn drop(&mut self) {
+        let raw = self.0;
+        let ptr = raw.header as *const ();
+
+        unsafe {
+            let state = (*raw.header)
+                .state
+                .update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                    s.clear_flag(flags::RUNNING | flags::SCHEDULED)
+                        .set_flag(flags::CLOSED)
+                });
+
+            Raw::<F, T, S, M, E>::drop_future(ptr);
+
+            let awaiter = if state.has_awaiter() {
+                (*raw.header).take(None)
+            } else {
+                None
+            };
+
+            Raw::<F, T, S, M, E>::drop_reference(ptr);
+
+            if let Some(w) = awaiter {
+                utils::abort_on_panic(|| w.wake());
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    extern crate std;
+
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::{Context, Poll, Waker};
+    use std::sync::{Arc, Mutex};
+
+    use crate::builder::Builder;
+    use crate::task::Task;
+
+    /// returns `Pending` until it has been polled `n` times, storing the last waker it saw
+    struct Yield {
+        remaining: usize,
+        waker: Arc<Mutex<Option<Waker>>>,
+    }
+
+    impl Future for Yield {
+        type Output = usize;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+            if self.remaining == 0 {
+                return Poll::Ready(7);
+            }
+            self.remaining -= 1;
+            *self.waker.lock().unwrap() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn run_to_completion() {
+        let (sender, recv) = flume::unbounded();
+        let (task, handle) =
+            Builder::new().spawn(|()| async { 1 + 2 }, move |task| sender.send(task).unwrap());
+
+        assert!(task.state().is_scheduled());
+        task.schedule();
+
+        let task: Task = recv.recv().unwrap();
+        assert!(!task.run());
+        assert!(recv.try_recv().is_err());
+
+        assert!(handle.is_finished());
+        assert_eq!(handle.join(), Some(3));
+    }
+
+    #[test]
+    fn wake_schedules_once() {
+        let scheduled = Arc::new(AtomicUsize::new(0));
+        let slot = Arc::new(Mutex::new(None));
+        let (sender, recv) = flume::unbounded();
+
+        let counter = scheduled.clone();
+        let (task, _) = Builder::new().spawn(
+            |()| Yield {
+                remaining: 2,
+                waker: slot.clone(),
+            },
+            move |task| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                sender.send(task).unwrap();
+            },
+        );
+
+        assert!(!task.run());
+        assert_eq!(scheduled.load(Ordering::SeqCst), 0);
+
+        let waker = slot.lock().unwrap().take().unwrap();
+        waker.wake_by_ref();
+        waker.wake_by_ref();
+        waker.wake();
+        assert_eq!(scheduled.load(Ordering::SeqCst), 1);
+
+        let task: Task = recv.recv().unwrap();
+        assert!(!task.run());
+        assert!(recv.try_recv().is_err());
+
+        slot.lock().unwrap().take().unwrap().wake();
+        assert_eq!(scheduled.load(Ordering::SeqCst), 2);
+
+        let task: Task = recv.recv().unwrap();
+        assert!(!task.run());
+    }
+
+    #[test]
+    fn freed_after_last_reference() {
+        let alive = Arc::new(());
+        let slot = Arc::new(Mutex::new(None));
+
+        let guard = alive.clone();
+        let (task, _) = Builder::new().spawn(
+            |()| Yield {
+                remaining: 1,
+                waker: slot.clone(),
+            },
+            move |task: Task| {
+                let _ = &guard;
+                drop(task);
+            },
+        );
+
+        assert!(!task.run());
+        assert_eq!(Arc::strong_count(&alive), 2);
+
+        // the scheduler drops the task, closing it, then the waker goes away
+        let waker = slot.lock().unwrap().take().unwrap();
+        waker.wake();
+        assert_eq!(Arc::strong_count(&alive), 1);
+    }
+
+    #[test]
+    fn dropping_last_waker_drops_future() {
+        let alive = Arc::new(());
+        let slot = Arc::new(Mutex::new(None));
+        let (sender, recv) = flume::unbounded();
+
+        let guard = alive.clone();
+        let (task, _) = Builder::new().spawn(
+            |()| {
+                let guard = guard;
+                let mut fut = Yield {
+                    remaining: 1,
+                    waker: slot.clone(),
+                };
+                core::future::poll_fn(move |cx| {
+                    let _ = &guard;
+                    Pin::new(&mut fut).poll(cx)
+                })
+            },
+            move |task| sender.send(task).unwrap(),
+        );
+
+        assert!(!task.run());
+        assert_eq!(Arc::strong_count(&alive), 2);
+
+        drop(slot.lock().unwrap().take());
+        let task: Task = recv.recv().unwrap();
+        assert!(task.state().is_closed());
+        assert!(!task.run());
+        assert_eq!(Arc::strong_count(&alive), 1);
+    }
+
+    #[test]
+    fn join_from_another_thread() {
+        let slot = Arc::new(Mutex::new(None));
+        let (sender, recv) = flume::unbounded();
+
+        let (task, handle) = Builder::new().spawn(
+            |()| Yield {
+                remaining: 1,
+                waker: slot.clone(),
+            },
+            move |task| sender.send(task).unwrap(),
+        );
+        assert!(!task.run());
+
+        let joiner = std::thread::spawn(move || handle.join());
+
+        slot.lock().unwrap().take().unwrap().wake();
+        let task: Task = recv.recv().unwrap();
+        assert!(!task.run());
+
+        assert_eq!(joiner.join().unwrap(), Some(7));
+    }
+
+    #[test]
+    fn await_handle_wakes_awaiter() {
+        let (sender, recv) = flume::unbounded();
+        let slot = Arc::new(Mutex::new(None));
+
+        let schedule = move |task| sender.send(task).unwrap();
+        let (task, handle) = Builder::new().spawn(
+            |()| Yield {
+                remaining: 1,
+                waker: slot.clone(),
+            },
+            schedule.clone(),
+        );
+        let (awaiter, joined) = Builder::new().spawn(|()| handle, schedule);
+
+        assert!(!task.run());
+        assert!(!awaiter.run());
+        assert!(recv.try_recv().is_err());
+
+        // completing the first task should wake the one awaiting it
+        slot.lock().unwrap().take().unwrap().wake();
+        let task: Task = recv.recv().unwrap();
+        assert!(!task.run());
+
+        let awaiter: Task = recv.recv().unwrap();
+        assert!(!awaiter.run());
+        assert_eq!(joined.join(), Some(7));
+    }
+
+    #[test]
+    fn join_closed_task() {
+        let (task, handle) = Builder::new().spawn(|()| async { 1 }, |_| {});
+        drop(task);
+        assert!(handle.state().is_closed());
+        assert_eq!(handle.join(), None);
+    }
+
+    #[test]
+    fn detached_output_is_dropped() {
+        let alive = Arc::new(());
+
+        let guard = alive.clone();
+        let (task, handle) = Builder::new().spawn(|()| async move { guard }, |_| {});
+        drop(handle);
+        assert!(!task.run());
+        assert_eq!(Arc::strong_count(&alive), 1);
+
+        let guard = alive.clone();
+        let (task, handle) = Builder::new().spawn(|()| async move { guard }, |_| {});
+        assert!(!task.run());
+        assert_eq!(Arc::strong_count(&alive), 2);
+        drop(handle);
+        assert_eq!(Arc::strong_count(&alive), 1);
+    }
+
+    #[test]
+    fn cancel_drops_future() {
+        let alive = Arc::new(());
+        let slot = Arc::new(Mutex::new(None));
+        let (sender, recv) = flume::unbounded();
+
+        let guard = alive.clone();
+        let (task, handle) = Builder::new().spawn(
+            |()| {
+                let guard = guard;
+                let mut fut = Yield {
+                    remaining: 1,
+                    waker: slot.clone(),
+                };
+                core::future::poll_fn(move |cx| {
+                    let _ = &guard;
+                    Pin::new(&mut fut).poll(cx)
+                })
+            },
+            move |task| sender.send(task).unwrap(),
+        );
+        assert!(!task.run());
+
+        // the task isn't scheduled, so cancelling has to schedule it one last time
+        assert_eq!(handle.cancel(), None);
+        let task: Task = recv.recv().unwrap();
+        assert!(task.state().is_closed());
+        assert_eq!(Arc::strong_count(&alive), 2);
+        assert!(!task.run());
+        assert_eq!(Arc::strong_count(&alive), 1);
+
+        // waking a cancelled task does nothing
+        slot.lock().unwrap().take().unwrap().wake();
+        assert!(recv.try_recv().is_err());
+    }
+
+    #[test]
+    fn cancel_completed_returns_output() {
+        let (task, handle) = Builder::new().spawn(|()| async { 5 }, |_| {});
+        assert!(!task.run());
+        assert_eq!(handle.cancel(), Some(5));
+    }
+
+    #[test]
+    fn detach_runs_to_completion() {
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let counter = done.clone();
+        let (task, handle) = Builder::new().spawn(
+            |()| async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+            |_| {},
+        );
+        handle.detach();
+        assert!(!task.run());
+        assert_eq!(done.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_is_captured() {
+        let (task, handle) = Builder::new().spawn(|()| async { panic!("boom") }, |_| {});
+        assert!(!task.run());
+        assert!(handle.state().is_completed());
+
+        let panic = handle.try_join().unwrap_err().into_panic().unwrap();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"boom"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_is_resumed_by_join() {
+        let (task, handle) = Builder::new().spawn(|()| async { panic!("boom") }, |_| {});
+        assert!(!task.run());
+
+        let joined = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| handle.join()));
+        assert!(joined.is_err());
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn panic_closes_task() {
+        let (task, handle) = Builder::new().spawn(|()| async { panic!("boom") }, |_| {});
+        let ran = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| task.run()));
+        assert!(ran.is_err());
+        assert!(handle.state().is_closed());
+        assert!(handle.try_join().unwrap_err().is_cancelled());
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(u8)]
+    enum Priority {
+        Low = 1,
+        High = 2,
+    }
+
+    crate::impl_tag!(Priority as u8 { Low, High });
+
+    #[test]
+    fn metadata_and_tag() {
+        let (sender, recv) = flume::unbounded();
+        let (task, handle) = Builder::new().metadata(42usize).tag(Priority::Low).spawn(
+            |id| {
+                let id = *id;
+                async move { id }
+            },
+            move |task| sender.send(task).unwrap(),
+        );
+
+        assert_eq!(*task.metadata(), 42);
+        assert_eq!(*handle.metadata(), 42);
+        assert_eq!(task.tag::<Priority>(), Priority::Low);
+
+        // the tag can be changed while the task is sitting in a queue
+        task.schedule();
+        let old = handle.update_tag(|_| Priority::High);
+        assert_eq!(old, Priority::Low);
+
+        let task: Task<usize> = recv.recv().unwrap();
+        assert_eq!(task.tag::<Priority>(), Priority::High);
+        assert!(task.state().is_scheduled());
+        assert!(!task.run());
+
+        assert_eq!(handle.tag::<Priority>(), Priority::High);
+        assert_eq!(handle.join(), Some(42));
+    }
+
+    // without `std`, the current task is tracked globally, which the parallel test harness would race on
+    #[cfg(feature = "std")]
+    crate::task_local! {
+        static COMPONENT_ID: usize;
+        static FRAME: core::cell::Cell<u64>;
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn task_locals() {
+        let (sender, recv) = flume::unbounded();
+        let slot = Arc::new(Mutex::new(None));
+
+        let (task, handle) = Builder::new()
+            .local(&COMPONENT_ID, 3)
+            .local(&FRAME, core::cell::Cell::new(0))
+            .spawn(
+                |()| {
+                    let mut fut = Yield {
+                        remaining: 1,
+                        waker: slot.clone(),
+                    };
+                    core::future::poll_fn(move |cx| {
+                        FRAME.with(|frame| frame.set(frame.get() + 1));
+                        Pin::new(&mut fut).poll(cx)
+                    })
+                },
+                move |task| sender.send(task).unwrap(),
+            );
+
+        // a task spawned without the key can't see it
+        let (other, other_handle) =
+            Builder::new().spawn(|()| async { COMPONENT_ID.try_with(|id| *id) }, |_| {});
+
+        assert!(COMPONENT_ID.try_with(|_| ()).is_err());
+        assert!(!task.run());
+        assert!(!other.run());
+        assert_eq!(
+            other_handle.join(),
+            Some(Err(crate::local::AccessError::NotSet))
+        );
+
+        slot.lock().unwrap().take().unwrap().wake();
+        let task: Task = recv.recv().unwrap();
+        assert!(!task.run());
+        assert_eq!(handle.join(), Some(7));
+
+        let (task, handle) = Builder::new()
+            .local(&COMPONENT_ID, 3)
+            .local(&FRAME, core::cell::Cell::new(10))
+            .spawn(
+                |()| async { (COMPONENT_ID.get(), FRAME.with(|f| f.get())) },
+                |_| {},
+            );
+        assert!(!task.run());
+        assert_eq!(handle.join(), Some((3, 10)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn budget_forces_yield() {
+        let (sender, recv) = flume::unbounded();
+        let polls = Arc::new(AtomicUsize::new(0));
+
+        let counter = polls.clone();
+        let (task, handle) = Builder::new().spawn(
+            |()| async move {
+                // a loop that would never yield on its own
+                for _ in 0..300 {
+                    crate::coop::consume_budget().await;
+                }
+                counter.load(Ordering::SeqCst)
+            },
+            move |task| sender.send(task).unwrap(),
+        );
+
+        assert!(crate::coop::has_budget_remaining());
+        let mut task: Task = task;
+        loop {
+            polls.fetch_add(1, Ordering::SeqCst);
+            task.run();
+            match recv.try_recv() {
+                Ok(next) => task = next,
+                Err(_) => break,
+            }
+        }
+
+        // 300 operations with a budget of 128 takes 3 polls
+        assert_eq!(handle.join(), Some(3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn scope_waits_for_children() {
+        let (sender, recv) = flume::unbounded();
+        let slot = Arc::new(Mutex::new(None));
+
+        let waker = slot.clone();
+        let (task, handle) = Builder::new().spawn(
+            |()| async move {
+                let mut hits = [0; 3];
+                let total = crate::scope::scope(|s| {
+                    for (i, hit) in hits.iter_mut().enumerate() {
+                        // detached children still have to finish before the scope does
+                        s.spawn(async move { *hit = i + 1 }).detach();
+                    }
+                    let slow = Builder::new().metadata("slow").spawn_scoped(&s, |name| {
+                        assert_eq!(*name, "slow");
+                        Yield {
+                            remaining: 1,
+                            waker,
+                        }
+                    });
+                    assert_eq!(*slow.metadata(), "slow");
+                    slow
+                })
+                .await;
+                (total, hits)
+            },
+            move |task| sender.send(task).unwrap(),
+        );
+
+        assert!(!task.run());
+        assert!(!handle.is_finished());
+
+        slot.lock().unwrap().take().unwrap().wake();
+        while let Ok(task) = recv.try_recv() {
+            task.run();
+        }
+        assert_eq!(handle.join(), Some((7, [1, 2, 3])));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn dropping_scope_cancels_children() {
+        struct Guard<'a>(&'a AtomicUsize);
+
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        let slot = Arc::new(Mutex::new(None));
+        let dropped = &dropped;
+
+        {
+            let waker = slot.clone();
+            let mut fut = core::pin::pin!(crate::scope::scope(|s| {
+                for _ in 0..2 {
+                    let waker = waker.clone();
+                    s.spawn(async move {
+                        let _guard = Guard(dropped);
+                        Yield {
+                            remaining: 1,
+                            waker,
+                        }
+                        .await
+                    })
+                    .detach();
+                }
+                async {}
+            }));
+
+            let waker = Waker::noop();
+            let mut cx = Context::from_waker(waker);
+            assert!(fut.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        }
+
+        // both children are dropped along with the scope, even though neither finished
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+        drop(slot.lock().unwrap().take());
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn registry_tracks_live_tasks() {
+        let registry = crate::registry::Registry::new();
+        let slot = Arc::new(Mutex::new(None));
+        let (sender, recv) = flume::unbounded();
+
+        let waker = slot.clone();
+        let line = line!() + 1;
+        let (stuck, stuck_handle) = Builder::new().metadata("stuck").registry(&registry).spawn(
+            |_| Yield {
+                remaining: 2,
+                waker,
+            },
+            move |task| sender.send(task).unwrap(),
+        );
+        let (done, done_handle) = Builder::new()
+            .metadata("done")
+            .registry(&registry)
+            .spawn(|_| async { 1 }, |_| {});
+        // tasks without a registry aren't tracked
+        let (other, _) = Builder::new().metadata("other").spawn(|_| async {}, |_| {});
+
+        assert!(!done.run());
+        assert!(!other.run());
+        assert_eq!(done_handle.join(), Some(1));
+
+        assert!(!stuck.run());
+        slot.lock().unwrap().take().unwrap().wake();
+        let stuck: Task<&str> = recv.recv().unwrap();
+        assert!(!stuck.run());
+
+        let tasks = registry.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, 0);
+        assert_eq!(tasks[0].metadata, "stuck");
+        assert_eq!(tasks[0].polls, 2);
+        assert_eq!(tasks[0].location.file(), file!());
+        assert_eq!(tasks[0].location.line(), line);
+        assert!(!tasks[0].state.is_completed());
+
+        // the last waker reschedules the task one last time, so its future can be dropped
+        drop(stuck_handle);
+        drop(slot.lock().unwrap().take());
+        assert!(!registry.is_empty());
+        assert!(!recv.recv().unwrap().run());
+        assert!(registry.is_empty());
+    }
+
+    /// checks that every field of the task allocation is aligned and in bounds
+    fn check_layout<F, S>()
+    where
+        F: Future,
+        S: crate::Schedule<()>,
+    {
+        let layout = crate::Raw::<F, F::Output, S, ()>::eval_task_layout();
+        let header = core::mem::size_of::<crate::Header<()>>();
+
+        assert!(layout.offset_s >= header);
+        assert_eq!(layout.offset_s % core::mem::align_of::<S>(), 0);
+        assert!(layout.offset_s + core::mem::size_of::<S>() <= layout.offset_f);
+
+        assert_eq!(layout.offset_f, layout.offset_r);
+        assert_eq!(layout.offset_f % core::mem::align_of::<F>(), 0);
+        assert_eq!(
+            layout.offset_r % core::mem::align_of::<Result<F::Output, crate::Panic>>(),
+            0
+        );
+        assert!(layout.offset_f + core::mem::size_of::<F>() <= layout.layout.size());
+        assert!(
+            layout.offset_r + core::mem::size_of::<Result<F::Output, crate::Panic>>()
+                <= layout.layout.size()
+        );
+        assert_eq!(layout.layout.align() % core::mem::align_of::<F>(), 0);
+        assert_eq!(layout.layout.align() % core::mem::align_of::<S>(), 0);
+    }
+
+    #[repr(align(64))]
+    struct Aligned([u8; 3]);
+
+    #[test]
+    fn task_layout() {
+        check_layout::<core::future::Ready<()>, fn(Task)>();
+        check_layout::<core::future::Ready<u8>, fn(Task)>();
+        check_layout::<core::future::Ready<Aligned>, fn(Task)>();
+        check_layout::<core::future::Pending<u128>, std::boxed::Box<dyn Fn(Task) + Send + Sync>>();
+
+        // the pointers handed out by `from_ptr` have to be usable for the task we just allocated
+        let (task, handle) = Builder::new().spawn(|()| async { Aligned([1, 2, 3]) }, |_: Task| {});
+        assert!(!task.run());
+        let out = handle.join().unwrap();
+        assert_eq!(&out as *const Aligned as usize % 64, 0);
+        assert_eq!(out.0, [1, 2, 3]);
+    }
+
+    /// bumps a counter when dropped
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn futures_and_outputs_dropped_once() {
+        let future = Arc::new(AtomicUsize::new(0));
+        let output = Arc::new(AtomicUsize::new(0));
+        let schedule = Arc::new(AtomicUsize::new(0));
+
+        let spawn = || {
+            let f = Counted(future.clone());
+            let o = output.clone();
+            let s = Counted(schedule.clone());
+            Builder::new().spawn(
+                |()| async move {
+                    let _f = f;
+                    Counted(o)
+                },
+                move |_| {
+                    let _ = &s;
+                },
+            )
+        };
+
+        // completed, and the output is taken by the handle
+        let (task, handle) = spawn();
+        task.run();
+        drop(handle.join());
+        assert_eq!(future.load(Ordering::SeqCst), 1);
+        assert_eq!(output.load(Ordering::SeqCst), 1);
+        assert_eq!(schedule.load(Ordering::SeqCst), 1);
+
+        // completed without a handle, so the task drops the output
+        let (task, handle) = spawn();
+        handle.detach();
+        task.run();
+        assert_eq!(future.load(Ordering::SeqCst), 2);
+        assert_eq!(output.load(Ordering::SeqCst), 2);
+        assert_eq!(schedule.load(Ordering::SeqCst), 2);
+
+        // completed, then the handle is dropped with the output still inside
+        let (task, handle) = spawn();
+        task.run();
+        drop(handle);
+        assert_eq!(future.load(Ordering::SeqCst), 3);
+        assert_eq!(output.load(Ordering::SeqCst), 3);
+        assert_eq!(schedule.load(Ordering::SeqCst), 3);
+
+        // never run
+        let (task, handle) = spawn();
+        drop(task);
+        assert!(matches!(
+            handle.try_join(),
+            Err(crate::handle::JoinError::Cancelled)
+        ));
+        assert_eq!(future.load(Ordering::SeqCst), 4);
+        assert_eq!(output.load(Ordering::SeqCst), 3);
+        assert_eq!(schedule.load(Ordering::SeqCst), 4);
     }
 }
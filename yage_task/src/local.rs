@@ -0,0 +1,207 @@
+use core::any::Any;
+use core::marker::PhantomData;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// the task-local values of a task, stored in its header.
+/// each value is keyed by the address of its `LocalKey`
+pub(crate) struct Locals {
+    entries: Vec<(usize, Box<dyn Any + Send>)>,
+}
+
+impl Locals {
+    pub(crate) const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn insert<T: Send + 'static>(&mut self, key: &'static LocalKey<T>, value: T) {
+        let id = key.id();
+        match self.entries.iter_mut().find(|(k, _)| *k == id) {
+            Some((_, slot)) => *slot = Box::new(value),
+            None => self.entries.push((id, Box::new(value))),
+        }
+    }
+
+    fn get<T: 'static>(&self, key: &'static LocalKey<T>) -> Option<&T> {
+        let id = key.id();
+        self.entries
+            .iter()
+            .find(|(k, _)| *k == id)
+            .and_then(|(_, value)| value.downcast_ref())
+    }
+}
+
+/// a key for a task-local value, declared with `task_local!`.
+///
+/// values are set when the task is spawned with `Builder::local`,
+/// and can be read from anywhere inside the future of that task while it is being polled
+pub struct LocalKey<T: 'static> {
+    // statics need a non-zero size to get a unique address
+    _unique: u8,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        Self {
+            _unique: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    fn id(&'static self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// calls `f` with a reference to the value of this key in the current task.
+    ///
+    /// panics if called outside of a task, or if the task wasn't spawned with a value for this key
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.try_with(f)
+            .expect("cannot access a task-local value that isn't set")
+    }
+
+    /// the same as `LocalKey::with`, but returns an error instead of panicking
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let locals = current::get();
+        if locals.is_null() {
+            return Err(AccessError::NoTask);
+        }
+
+        // SAFETY: the pointer is only set while the task that owns it is being polled
+        match unsafe { (*locals).get(self) } {
+            Some(value) => Ok(f(value)),
+            None => Err(AccessError::NotSet),
+        }
+    }
+}
+
+impl<T: Copy + 'static> LocalKey<T> {
+    /// returns a copy of the value of this key in the current task
+    pub fn get(&'static self) -> T {
+        self.with(|value| *value)
+    }
+}
+
+impl<T: 'static> core::fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LocalKey").finish_non_exhaustive()
+    }
+}
+
+/// the reason a task-local value couldn't be accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessError {
+    /// no task is currently being polled on this thread
+    NoTask,
+    /// the current task wasn't spawned with a value for this key
+    NotSet,
+}
+
+impl core::fmt::Display for AccessError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NoTask => f.write_str("not inside of a task"),
+            Self::NotSet => f.write_str("task-local value not set"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AccessError {}
+
+/// marks the locals of a task as current while it is being polled,
+/// restoring the previous ones (if a task is polled inside of another) when dropped
+pub(crate) struct Enter {
+    prev: *const Locals,
+}
+
+impl Enter {
+    pub(crate) fn new(locals: *const Locals) -> Self {
+        Self {
+            prev: current::replace(locals),
+        }
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        current::replace(self.prev);
+    }
+}
+
+#[cfg(feature = "std")]
+mod current {
+    use super::Locals;
+    use core::cell::Cell;
+
+    #[cfg(not(loom))]
+    std::thread_local! {
+        static CURRENT: Cell<*const Locals> = const { Cell::new(core::ptr::null()) };
+    }
+
+    // loom runs every modelled thread on the same OS thread
+    #[cfg(loom)]
+    loom::thread_local! {
+        static CURRENT: Cell<*const Locals> = Cell::new(core::ptr::null());
+    }
+
+    pub(super) fn get() -> *const Locals {
+        CURRENT.with(Cell::get)
+    }
+
+    pub(super) fn replace(locals: *const Locals) -> *const Locals {
+        CURRENT.with(|current| current.replace(locals))
+    }
+}
+
+/// without `std` there are no thread locals, so this assumes tasks are only ever polled from one core
+#[cfg(not(feature = "std"))]
+mod current {
+    use super::Locals;
+    use core::sync::atomic::{AtomicPtr, Ordering};
+
+    static CURRENT: AtomicPtr<Locals> = AtomicPtr::new(core::ptr::null_mut());
+
+    pub(super) fn get() -> *const Locals {
+        CURRENT.load(Ordering::Acquire)
+    }
+
+    pub(super) fn replace(locals: *const Locals) -> *const Locals {
+        CURRENT.swap(locals as *mut Locals, Ordering::AcqRel)
+    }
+}
+
+/// declares one or more task-local keys.
+///
+/// ```
+/// yage_task::task_local! {
+///     pub static COMPONENT_ID: usize;
+///     static FRAME: u64;
+/// }
+///
+/// let (task, handle) = yage_task::builder::Builder::new()
+///     .local(&COMPONENT_ID, 7)
+///     .spawn(|()| async { COMPONENT_ID.get() }, |_| {});
+/// task.run();
+/// assert_eq!(handle.join(), Some(7));
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::local::LocalKey<$ty> = $crate::local::LocalKey::new();
+        $crate::task_local!($($rest)*);
+    };
+}
@@ -0,0 +1,206 @@
+//! models the races between running, waking, cancelling and joining a task.
+//!
+//! run with `RUSTFLAGS="--cfg loom" cargo test --release --features std loom_tests`
+
+extern crate std;
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::Ordering;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::task::Wake;
+
+use loom::sync::atomic::{AtomicBool, AtomicUsize};
+use loom::sync::{Arc, Mutex};
+use loom::thread;
+
+use crate::builder::Builder;
+use crate::handle::JoinHandle;
+use crate::state::{AtomicState, State};
+use crate::task::Task;
+
+type Queue = Arc<Mutex<VecDeque<Task>>>;
+
+/// spawns `future` with a scheduler that pushes to the returned queue
+fn spawn<F>(future: F) -> (Task, JoinHandle<F::Output>, Queue)
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let queue: Queue = Arc::new(Mutex::new(VecDeque::new()));
+    let sender = queue.clone();
+    let (task, handle) = Builder::new().spawn(
+        |()| future,
+        move |task| sender.lock().unwrap().push_back(task),
+    );
+    (task, handle, queue)
+}
+
+/// runs everything in the queue until it's empty.
+/// this also frees any task that was only kept alive by the queue
+fn drain(queue: &Queue) {
+    loop {
+        let task = queue.lock().unwrap().pop_front();
+        match task {
+            Some(task) => {
+                task.run();
+            }
+            None => break,
+        }
+    }
+}
+
+/// pending until the flag is set
+struct WaitFor(Arc<AtomicBool>);
+
+impl Future for WaitFor {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.0.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// never completes, and counts how many times it was dropped
+struct Forever(Arc<AtomicUsize>);
+
+impl Future for Forever {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        Poll::Pending
+    }
+}
+
+impl Drop for Forever {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct Notified(AtomicBool);
+
+impl Wake for Notified {
+    fn wake(self: std::sync::Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn state_updates_are_atomic() {
+    loom::model(|| {
+        let state = Arc::new(AtomicState::new(State {
+            reference_count: 0,
+            flags: 0,
+            tag: 0,
+        }));
+
+        let other = state.clone();
+        let t = thread::spawn(move || {
+            other.update(
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                State::increment_reference_count,
+            );
+        });
+        state.update(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            State::increment_reference_count,
+        );
+        t.join().unwrap();
+
+        assert_eq!(state.load(Ordering::Acquire).reference_count, 2);
+    });
+}
+
+#[test]
+fn wake_while_running_is_not_lost() {
+    loom::model(|| {
+        let ready = Arc::new(AtomicBool::new(false));
+        let (task, handle, queue) = spawn(WaitFor(ready.clone()));
+
+        let waker = task.waker();
+        let t = thread::spawn(move || {
+            ready.store(true, Ordering::Release);
+            waker.wake();
+        });
+
+        task.run();
+        t.join().unwrap();
+        drain(&queue);
+
+        assert!(handle.is_finished());
+        assert!(handle.try_join().is_ok());
+    });
+}
+
+#[test]
+fn cancel_while_running() {
+    loom::model(|| {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (task, handle, queue) = spawn(Forever(dropped.clone()));
+
+        let t = thread::spawn(move || handle.cancel());
+
+        task.run();
+        assert!(t.join().unwrap().is_none());
+        drain(&queue);
+
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn join_while_completing() {
+    loom::model(|| {
+        let (task, mut handle, _queue) = spawn(async { 7 });
+
+        let t = thread::spawn(move || {
+            task.run();
+        });
+
+        let notified = std::sync::Arc::new(Notified(AtomicBool::new(false)));
+        let waker = Waker::from(notified.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let first = handle.poll_join(&mut cx);
+        t.join().unwrap();
+
+        match first {
+            Poll::Ready(out) => assert_eq!(out.unwrap(), 7),
+            Poll::Pending => {
+                // the task completed after we registered, so we must have been woken
+                assert!(notified.0.load(Ordering::SeqCst));
+                match handle.poll_join(&mut cx) {
+                    Poll::Ready(out) => assert_eq!(out.unwrap(), 7),
+                    Poll::Pending => panic!("completed task is still pending"),
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn drop_last_waker_and_handle() {
+    loom::model(|| {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (task, handle, queue) = spawn(Forever(dropped.clone()));
+
+        let waker = task.waker();
+        assert!(!task.run());
+
+        let t = thread::spawn(move || drop(waker));
+        drop(handle);
+        t.join().unwrap();
+
+        // whichever went last rescheduled the task, so the future can be dropped
+        drain(&queue);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    });
+}
@@ -0,0 +1,179 @@
+//! an opt-in registry of live tasks, for debugging.
+//!
+//! tasks spawned with `Builder::registry` are tracked until they are freed,
+//! and `Registry::tasks` returns a snapshot of every one of them.
+//! this is meant for finding out which task is stuck, so it favours simplicity over speed
+
+use core::marker::PhantomData;
+use core::panic::Location;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::sync::Mutex;
+
+use crate::header::{Header, Tag};
+use crate::state::State;
+
+/// a registry of live tasks with metadata `M`
+pub struct Registry<M> {
+    pub(crate) inner: Arc<Inner>,
+    _marker: PhantomData<fn() -> M>,
+}
+
+pub(crate) struct Inner {
+    next_id: AtomicU64,
+    tasks: Mutex<BTreeMap<u64, TaskPtr>>,
+}
+
+#[derive(Clone, Copy)]
+struct TaskPtr(NonNull<()>);
+
+// SAFETY: the pointer is only dereferenced while the registry lock is held,
+// and tasks remove themselves (under that lock) before they are freed
+unsafe impl Send for TaskPtr {}
+
+impl<M> Registry<M> {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                next_id: AtomicU64::new(0),
+                tasks: Mutex::new(BTreeMap::new()),
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// the number of live tasks in this registry
+    pub fn len(&self) -> usize {
+        self.inner.tasks.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// takes a snapshot of every live task, ordered by id
+    pub fn tasks(&self) -> Vec<TaskSnapshot<M>>
+    where
+        M: Clone,
+    {
+        let tasks = self.inner.tasks.lock().unwrap();
+        tasks
+            .values()
+            .map(|task| {
+                // SAFETY: every task in this registry has metadata `M` (see `Builder::registry`),
+                // and it can't be freed while we hold the lock
+                let header = unsafe { &*(task.0.as_ptr() as *const Header<M>) };
+                let entry = header
+                    .entry
+                    .as_ref()
+                    .expect("registered task without an entry");
+
+                TaskSnapshot {
+                    id: entry.id,
+                    metadata: header.metadata.clone(),
+                    state: header.state.load(Ordering::Acquire),
+                    location: entry.location,
+                    polls: entry.polls.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+}
+
+impl<M> Default for Registry<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> Clone for Registry<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M> core::fmt::Debug for Registry<M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Registry")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// the bookkeeping kept in the header of a registered task
+pub(crate) struct Entry {
+    id: u64,
+    location: &'static Location<'static>,
+    pub(crate) polls: AtomicU64,
+    registry: Arc<Inner>,
+}
+
+impl Entry {
+    pub(crate) fn new(registry: Arc<Inner>, location: &'static Location<'static>) -> Self {
+        Self {
+            id: registry.next_id.fetch_add(1, Ordering::Relaxed),
+            location,
+            polls: AtomicU64::new(0),
+            registry,
+        }
+    }
+
+    /// adds the task at `ptr` to the registry
+    ///
+    /// SAFETY: `ptr` must be the task that owns this entry, and it must call `Entry::remove` before it is freed
+    pub(crate) unsafe fn insert(&self, ptr: NonNull<()>) {
+        self.registry
+            .tasks
+            .lock()
+            .unwrap()
+            .insert(self.id, TaskPtr(ptr));
+    }
+
+    /// removes the task from the registry.
+    /// this has to happen before anything in the header is dropped
+    pub(crate) fn remove(&self) {
+        self.registry.tasks.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// a snapshot of a live task, see `Registry::tasks`
+#[derive(Clone)]
+pub struct TaskSnapshot<M> {
+    /// unique (per registry) id of the task, in the order it was spawned
+    pub id: u64,
+    pub metadata: M,
+    pub state: State,
+    /// where the task was spawned
+    pub location: &'static Location<'static>,
+    /// how many times the future has been polled
+    pub polls: u64,
+}
+
+impl<M> TaskSnapshot<M> {
+    /// the tag of the task when the snapshot was taken.
+    ///
+    /// `E` should be the same type the task was spawned with, see `Builder::tag`
+    pub fn tag<E: Tag>(&self) -> E {
+        E::from_u16(self.state.tag)
+    }
+}
+
+impl<M: core::fmt::Debug> core::fmt::Debug for TaskSnapshot<M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TaskSnapshot")
+            .field("id", &self.id)
+            .field("metadata", &self.metadata)
+            .field("state", &self.state)
+            .field("tag", &self.state.tag)
+            .field("location", &format_args!("{}", self.location))
+            .field("polls", &self.polls)
+            .finish()
+    }
+}
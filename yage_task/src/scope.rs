@@ -0,0 +1,340 @@
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::sync::atomic::Ordering;
+use core::task::{Context, Poll, Waker};
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::sync::Mutex;
+
+use crate::builder::Builder;
+use crate::handle::{JoinError, JoinHandle};
+use crate::header::{Header, Tag};
+use crate::state::State;
+use crate::task::Task;
+
+/// creates a new scope for spawning tasks that can borrow from the stack.
+///
+/// children are polled by the returned future itself, on whatever thread polls it.
+/// it only resolves once `f` has resolved and every child has finished,
+/// and if it is dropped early, every child is cancelled and its future dropped before `drop` returns.
+///
+/// ```
+/// let (task, handle) = yage_task::builder::Builder::new().spawn(
+///     |()| async {
+///         let names = vec!["weapon", "mesh", "hud"];
+///         let names = &names;
+///
+///         yage_task::scope::scope(|s| async move {
+///             let handles: Vec<_> = names.iter().map(|name| s.spawn(async move { name.len() })).collect();
+///             let mut total = 0;
+///             for handle in handles {
+///                 total += handle.await;
+///             }
+///             total
+///         })
+///         .await
+///     },
+///     |task: yage_task::task::Task| {
+///         task.run();
+///     },
+/// );
+/// task.run();
+/// assert_eq!(handle.join(), Some(13));
+/// ```
+pub fn scope<'scope, F, Fut>(f: F) -> ScopeFuture<'scope, Fut>
+where
+    F: FnOnce(Scope<'scope>) -> Fut,
+    Fut: Future + 'scope,
+{
+    let scope = Scope {
+        shared: Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            children: Mutex::new(Vec::new()),
+            waker: Mutex::new(None),
+            closed: Mutex::new(false),
+        }),
+        _marker: PhantomData,
+    };
+
+    ScopeFuture {
+        shared: Arc::clone(&scope.shared),
+        body: Some(f(scope)),
+        output: None,
+        _marker: PhantomData,
+    }
+}
+
+/// a handle to a scope, used to spawn children into it
+pub struct Scope<'scope> {
+    shared: Arc<Shared>,
+    // invariant, so the scope can't be shortened or extended
+    _marker: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope> Clone for Scope<'scope> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'scope> Scope<'scope> {
+    /// spawns a child into this scope.
+    /// see `Builder::spawn_scoped` to spawn a child with metadata
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> ScopedJoinHandle<'scope, F::Output>
+    where
+        F: Future + Send + 'scope,
+        F::Output: Send + 'scope,
+    {
+        Builder::new().spawn_scoped(self, |()| future)
+    }
+}
+
+impl<M, E> Builder<M, E>
+where
+    E: Tag,
+{
+    /// spawns a task as a child of `scope`.
+    ///
+    /// unlike `Builder::spawn`, the future only has to live as long as the scope,
+    /// as the scope makes sure it is finished or dropped before the scope itself is
+    #[track_caller]
+    pub fn spawn_scoped<'scope, Fun, F>(
+        self,
+        scope: &Scope<'scope>,
+        future: Fun,
+    ) -> ScopedJoinHandle<'scope, F::Output, M>
+    where
+        Fun: FnOnce(&M) -> F,
+        F: Future + Send + 'scope,
+        F::Output: Send + 'scope,
+        M: Send + Sync + 'static,
+    {
+        let shared = Arc::clone(&scope.shared);
+        let schedule = move |task: Task<M>| shared.schedule(task.into_erased());
+
+        // SAFETY: `F` and its output are `Send`, and the scheduler is `Send + Sync`.
+        // the future is only ever polled or dropped by the `ScopeFuture`, which cancels and drops
+        // every child before it goes away. if the `ScopeFuture` is leaked instead,
+        // the children are leaked along with it, and never touched again
+        let (task, handle) = unsafe { self.spawn_unchecked(future, schedule) };
+
+        let closed = scope.shared.closed.lock().unwrap();
+        if *closed {
+            // the scope is gone, so nothing would ever run this.
+            // dropping the task here drops the future while everything it borrows is still alive
+            drop(closed);
+            drop(task);
+        } else {
+            scope.shared.children.lock().unwrap().push(Child {
+                ptr: task.ptr,
+                _waker: task.waker(),
+            });
+            // hold the lock until it's queued, so the scope can't be dropped in between
+            scope.shared.schedule(task.into_erased());
+            drop(closed);
+        }
+
+        ScopedJoinHandle {
+            inner: handle,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// a handle to a child of a scope.
+///
+/// like `JoinHandle`, dropping it detaches the child, which still has to finish before the scope does
+pub struct ScopedJoinHandle<'scope, T, M = ()> {
+    inner: JoinHandle<T, M>,
+    _marker: PhantomData<&'scope ()>,
+}
+
+impl<'scope, T, M> ScopedJoinHandle<'scope, T, M> {
+    /// a snapshot of the current state of this task
+    pub fn state(&self) -> State {
+        self.inner.state()
+    }
+
+    /// the metadata this task was spawned with
+    pub fn metadata(&self) -> &M {
+        self.inner.metadata()
+    }
+
+    /// returns `true` if the child has completed
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    /// polls for the output of the child, without resuming panics
+    pub fn poll_join(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, JoinError>> {
+        self.inner.poll_join(cx)
+    }
+
+    /// cancels the child, returning the output if it had already completed
+    pub fn cancel(self) -> Option<T> {
+        self.inner.cancel()
+    }
+
+    /// lets the child run to completion without a handle
+    pub fn detach(self) {
+        self.inner.detach()
+    }
+}
+
+impl<'scope, T, M> Future for ScopedJoinHandle<'scope, T, M> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
+
+impl<'scope, T, M> core::fmt::Debug for ScopedJoinHandle<'scope, T, M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("ScopedJoinHandle")
+            .field(&self.inner)
+            .finish()
+    }
+}
+
+/// the future returned by `scope`
+#[must_use = "futures do nothing unless polled"]
+pub struct ScopeFuture<'scope, Fut: Future> {
+    body: Option<Fut>,
+    output: Option<Fut::Output>,
+    shared: Arc<Shared>,
+    _marker: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope, Fut: Future> Future for ScopeFuture<'scope, Fut> {
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `body` is never moved out of, only dropped in place
+        let this = unsafe { self.get_unchecked_mut() };
+
+        this.shared.register(cx.waker());
+
+        if let Some(body) = this.body.as_mut()
+            && let Poll::Ready(out) = unsafe { Pin::new_unchecked(body) }.poll(cx)
+        {
+            this.output = Some(out);
+            this.body = None;
+        }
+
+        // only run the children queued so far, so one that keeps waking itself can't starve the body
+        let queued = this.shared.queue.lock().unwrap().len();
+        for _ in 0..queued {
+            let task = this.shared.queue.lock().unwrap().pop_front();
+            match task {
+                Some(task) => {
+                    task.run();
+                }
+                None => break,
+            }
+        }
+
+        if !this.shared.queue.lock().unwrap().is_empty() {
+            cx.waker().wake_by_ref();
+        }
+
+        let live = this.shared.prune();
+        match this.output.take() {
+            Some(out) if live == 0 => Poll::Ready(out),
+            out => {
+                this.output = out;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'scope, Fut: Future> Drop for ScopeFuture<'scope, Fut> {
+    fn drop(&mut self) {
+        // the body may be holding handles, so it goes first
+        self.body = None;
+
+        *self.shared.closed.lock().unwrap() = true;
+        let children = core::mem::take(&mut *self.shared.children.lock().unwrap());
+
+        for child in &children {
+            // SAFETY: the waker in `child` keeps the task alive
+            unsafe { child.header().cancel() };
+        }
+
+        // every child that still has a future is queued now, and running a closed task drops its future
+        loop {
+            let task = self.shared.queue.lock().unwrap().pop_front();
+            match task {
+                Some(task) => {
+                    task.run();
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<Task>>,
+    children: Mutex<Vec<Child>>,
+    waker: Mutex<Option<Waker>>,
+    closed: Mutex<bool>,
+}
+
+impl Shared {
+    fn register(&self, waker: &Waker) {
+        let mut slot = self.waker.lock().unwrap();
+        match &*slot {
+            Some(w) if w.will_wake(waker) => {}
+            _ => *slot = Some(waker.clone()),
+        }
+    }
+
+    fn schedule(&self, task: Task) {
+        self.queue.lock().unwrap().push_back(task);
+
+        let waker = self.waker.lock().unwrap().clone();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// forgets every child that doesn't have a future anymore, returning how many are left
+    fn prune(&self) -> usize {
+        let mut children = self.children.lock().unwrap();
+        children.retain(|child| !child.is_finished());
+        children.len()
+    }
+}
+
+/// a child of a scope.
+/// this keeps a waker around, so the task stays allocated until the scope is done with it
+struct Child {
+    ptr: NonNull<()>,
+    _waker: Waker,
+}
+
+// SAFETY: only the (atomic) state is ever accessed through `ptr`
+unsafe impl Send for Child {}
+
+impl Child {
+    fn header(&self) -> &Header<()> {
+        // SAFETY: `Header` is `#[repr(C)]` with the metadata last, so this only ever touches the
+        // fields shared by every header
+        unsafe { &*(self.ptr.as_ptr() as *const Header<()>) }
+    }
+
+    fn is_finished(&self) -> bool {
+        let state = self.header().state.load(Ordering::Acquire);
+        state.is_completed() || (state.is_closed() && !state.is_scheduled() && !state.is_running())
+    }
+}
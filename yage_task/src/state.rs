@@ -1,7 +1,7 @@
-use core::fmt::Pointer;
 use core::marker::PhantomData;
-use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::sync::atomic::Ordering;
 
+use crate::sync::AtomicU64;
 
 /// the state of a task
 /// this carries 3 fields: a reference count, flags, and a tag
@@ -11,7 +11,7 @@ use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 ///
 /// A note on tags:
 /// the `tag` field allows you to atomically store a 16-bit tag that could signify certain properties of a task
-/// by default, `Tag` is the unit type `()`, and has a value of 0 
+/// by default, `Tag` is the unit type `()`, and has a value of 0
 #[repr(C, align(8))]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct State {
@@ -53,13 +53,13 @@ impl State {
     #[inline]
     pub const fn increment_reference_count(mut self) -> Self {
         let count = self.reference_count;
-        self.reference_count = count.checked_add(1).expect("u16 overflow");
+        self.reference_count = count.checked_add(1).expect("u32 overflow");
         self
     }
 
     #[inline(always)]
     pub const fn decrement_reference_count(mut self) -> Self {
-        debug_assert!(self.reference_count == 0, "reference_count underflow");
+        debug_assert!(self.reference_count != 0, "reference_count underflow");
         self.reference_count -= 1;
         self
     }
@@ -94,6 +94,7 @@ unsafe impl Send for AtomicState {}
 unsafe impl Sync for AtomicState {}
 
 impl AtomicState {
+    #[cfg(not(loom))]
     pub const fn new(initial_state: State) -> Self {
         Self {
             value: AtomicU64::new(initial_state.as_usize()),
@@ -101,21 +102,13 @@ impl AtomicState {
         }
     }
 
-    pub fn with<F, R>(&self, load: Ordering, store: Ordering, f: F) -> R
-    where
-        F: FnOnce(&mut State) -> R,
-    {
-        let mut state = State::from_usize(self.value.load(load));
-        let result = f(&mut state);
-        self.value.store(state.as_usize(), store);
-        result
-    }
-
-    pub fn with_acquire_release<F, R>(&self, f: F) -> R
-    where
-        F: FnOnce(&mut State) -> R,
-    {
-        self.with(Ordering::Acquire, Ordering::Release, f)
+    /// loom's atomics can't be created in a const context
+    #[cfg(loom)]
+    pub fn new(initial_state: State) -> Self {
+        Self {
+            value: AtomicU64::new(initial_state.as_usize()),
+            _marker: PhantomData,
+        }
     }
 
     pub fn compare_exchange_weak(
@@ -131,6 +124,21 @@ impl AtomicState {
             .map_err(State::from_usize)
     }
 
+    /// repeatedly applies `f` to the current state until the compare-exchange succeeds.
+    /// returns the state as it was *before* `f` was applied
+    pub fn update<F>(&self, success: Ordering, failure: Ordering, mut f: F) -> State
+    where
+        F: FnMut(State) -> State,
+    {
+        let mut state = self.load(failure);
+        loop {
+            match self.compare_exchange_weak(state, f(state), success, failure) {
+                Ok(prev) => return prev,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
     pub fn load(&self, load: Ordering) -> State {
         State::from_usize(self.value.load(load))
     }
@@ -0,0 +1,8 @@
+//! the atomics the task state machine is built on.
+//! building with `--cfg loom` swaps them out for loom's, so the tests in `loom_tests` can model every interleaving
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::AtomicU64;
+
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::AtomicU64;
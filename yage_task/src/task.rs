@@ -1,7 +1,142 @@
 use core::marker::PhantomData;
+use core::mem;
 use core::ptr::NonNull;
+use core::sync::atomic::Ordering;
+use core::task::Waker;
 
+use crate::flags;
+use crate::header::{Header, Tag};
+use crate::state::State;
+
+/// a runnable task
+///
+/// holding a `Task` means the task is scheduled, and is waiting to be run.
+/// it is given to the `Schedule` function every time the task is woken,
+/// and consumed by either `Task::run` or `Task::schedule`
 pub struct Task<Meta = ()> {
     pub(crate) ptr: NonNull<()>,
     pub(crate) _marker: PhantomData<Meta>,
 }
+
+// SAFETY: the task state is only ever mutated atomically, and the future
+// is only polled through `Task::run`, which consumes the only `Task`
+unsafe impl<M: Send + Sync> Send for Task<M> {}
+unsafe impl<M: Send + Sync> Sync for Task<M> {}
+
+impl<M> Task<M> {
+    fn header(&self) -> *const Header<M> {
+        self.ptr.as_ptr() as *const Header<M>
+    }
+
+    /// a snapshot of the current state of this task
+    pub fn state(&self) -> State {
+        unsafe { (*self.header()).state.load(Ordering::Acquire) }
+    }
+
+    /// the metadata this task was spawned with
+    pub fn metadata(&self) -> &M {
+        unsafe { &(*self.header()).metadata }
+    }
+
+    /// the current tag of this task.
+    ///
+    /// `E` should be the same type the task was spawned with, see `Builder::tag`
+    pub fn tag<E: Tag>(&self) -> E {
+        unsafe { (*self.header()).tag() }
+    }
+
+    /// atomically replaces the tag of this task with the result of `f`, returning the old tag.
+    /// `f` may be called more than once if the state of the task changes concurrently
+    pub fn update_tag<E, F>(&self, f: F) -> E
+    where
+        E: Tag,
+        F: FnMut(E) -> E,
+    {
+        unsafe { (*self.header()).update_tag(f) }
+    }
+
+    /// passes this task to its `Schedule` function
+    pub fn schedule(self) {
+        let ptr = self.ptr.as_ptr();
+        let header = self.header();
+        mem::forget(self);
+
+        unsafe { ((*header).vtable.schedule)(ptr) }
+    }
+
+    /// polls the future of this task once.
+    ///
+    /// returns `true` if the task was woken while it was being polled,
+    /// in which case it has already been rescheduled
+    pub fn run(self) -> bool {
+        let ptr = self.ptr.as_ptr();
+        let header = self.header();
+        mem::forget(self);
+
+        unsafe { ((*header).vtable.run)(ptr) }
+    }
+
+    /// forgets the type of the metadata.
+    /// this is fine, because `Header` is `#[repr(C)]` with the metadata last
+    #[cfg(feature = "std")]
+    pub(crate) fn into_erased(self) -> Task {
+        let ptr = self.ptr;
+        mem::forget(self);
+        Task {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// creates a new `Waker` for this task
+    pub fn waker(&self) -> Waker {
+        let ptr = self.ptr.as_ptr();
+        let header = self.header();
+
+        unsafe { Waker::from_raw(((*header).vtable.clone_waker)(ptr)) }
+    }
+}
+
+impl<M> Drop for Task<M> {
+    fn drop(&mut self) {
+        let ptr = self.ptr.as_ptr();
+        let header = self.header();
+
+        unsafe {
+            // a dropped `Task` can never be run again, so the task is closed
+            (*header)
+                .state
+                .update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                    if s.is_completed() || s.is_closed() {
+                        s
+                    } else {
+                        s.set_flag(flags::CLOSED)
+                    }
+                });
+
+            ((*header).vtable.drop_future)(ptr);
+
+            let state = (*header)
+                .state
+                .update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                    s.clear_flag(flags::SCHEDULED)
+                });
+
+            // let whoever is waiting know the future is gone
+            if state.has_awaiter() {
+                (*header).notify(None);
+            }
+
+            ((*header).vtable.drop_reference)(ptr);
+        }
+    }
+}
+
+impl<M> core::fmt::Debug for Task<M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Task")
+            .field("ptr", &self.ptr)
+            .field("state", &self.state())
+            .finish()
+    }
+}
@@ -56,11 +56,9 @@ fn main() {
 
     dbg!(task.state());
 
-    let waker = task.waker();
-    let mut cx = core::task::Context::from_waker(&waker);
-    task.run(&mut cx);
+    task.run();
 
-    //println!("{:?}", handle.join());
+    println!("{:?}", handle.join());
 
     println!("Hello, world!");
 }
@@ -1,19 +1,37 @@
-use core::{cell::UnsafeCell, marker::PhantomData};
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::atomic::Ordering,
+};
 
 mod borrow;
 
 use borrow::BorrowFlag;
 
+/// a thread-safe `RefCell`.
+///
+/// instead of panicking on a conflicting borrow, this spins until the other borrow is released,
+/// so it is only meant for short critical sections
 pub struct Atomic<T: ?Sized> {
     borrow: BorrowFlag,
     value: UnsafeCell<T>,
 }
 
+// SAFETY: the borrow flag makes sure there is either one `AtomicMut`, or any number of `AtomicRef`s
+unsafe impl<T: ?Sized + Send> Send for Atomic<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for Atomic<T> {}
+
 pub struct AtomicRef<'a, T: ?Sized> {
+    borrow: &'a BorrowFlag,
+    value: NonNull<T>,
     _marker: PhantomData<&'a T>,
 }
 
 pub struct AtomicMut<'a, T: ?Sized> {
+    borrow: &'a BorrowFlag,
+    value: NonNull<T>,
     _marker: PhantomData<&'a mut T>,
 }
 
@@ -24,10 +42,139 @@ impl<T> Atomic<T> {
             value: UnsafeCell::new(val),
         }
     }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
 }
 
 impl<T: ?Sized> Atomic<T> {
+    /// borrows the value, waiting for a mutable borrow to be released
+    pub fn borrow(&self) -> AtomicRef<'_, T> {
+        loop {
+            if let Some(borrow) = self.try_borrow() {
+                return borrow;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// borrows the value, returning `None` if it is mutably borrowed
+    pub fn try_borrow(&self) -> Option<AtomicRef<'_, T>> {
+        let mut flag = self.borrow.load(Ordering::Relaxed);
+        loop {
+            if flag < 0 {
+                return None;
+            }
+            match self.borrow.compare_exchange_weak(
+                flag,
+                flag.checked_add(1).expect("too many borrows"),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    return Some(AtomicRef {
+                        borrow: &self.borrow,
+                        // SAFETY: `UnsafeCell::get` never returns null
+                        value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                        _marker: PhantomData,
+                    });
+                }
+                Err(f) => flag = f,
+            }
+        }
+    }
+
+    /// mutably borrows the value, waiting for every other borrow to be released
     pub fn borrow_mut(&self) -> AtomicMut<'_, T> {
-        todo!()
+        loop {
+            if let Some(borrow) = self.try_borrow_mut() {
+                return borrow;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// mutably borrows the value, returning `None` if it is borrowed
+    pub fn try_borrow_mut(&self) -> Option<AtomicMut<'_, T>> {
+        self.borrow
+            .compare_exchange(0, -1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| AtomicMut {
+                borrow: &self.borrow,
+                // SAFETY: `UnsafeCell::get` never returns null
+                value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                _marker: PhantomData,
+            })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for Atomic<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> Deref for AtomicRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: there are no mutable borrows while this exists
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> Drop for AtomicRef<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Deref for AtomicMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: this is the only borrow
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AtomicMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: this is the only borrow
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for AtomicMut<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrows_exclude_each_other() {
+        let atomic = Atomic::new(1);
+
+        let a = atomic.borrow();
+        let b = atomic.borrow();
+        assert!(atomic.try_borrow_mut().is_none());
+        assert_eq!(*a + *b, 2);
+        drop((a, b));
+
+        let mut m = atomic.borrow_mut();
+        assert!(atomic.try_borrow().is_none());
+        *m += 1;
+        drop(m);
+
+        assert_eq!(*atomic.borrow(), 2);
     }
 }
@@ -40,7 +40,7 @@ impl<T: ?Sized> Pointers<T> {
 
     const fn set_prev(&mut self, val: Option<NonNull<T>>) {
         unsafe {
-            (&raw mut (*self.inner.get()).next).write(val);
+            (&raw mut (*self.inner.get()).prev).write(val);
         }
     }
 
@@ -81,6 +81,10 @@ where
         }
     }
 
+    pub const fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
     pub fn push_front(&mut self, val: L::Handle) {
         let val = ManuallyDrop::new(val);
         let ptr = L::as_raw(&val);
@@ -102,6 +106,27 @@ where
         }
     }
 
+    pub fn push_back(&mut self, val: L::Handle) {
+        let val = ManuallyDrop::new(val);
+        let ptr = L::as_raw(&val);
+        assert_ne!(self.tail, Some(ptr));
+
+        unsafe {
+            L::pointers(ptr).as_mut().set_prev(self.tail);
+            L::pointers(ptr).as_mut().set_next(None);
+
+            if let Some(tail) = self.tail {
+                L::pointers(tail).as_mut().set_next(Some(ptr));
+            }
+
+            self.tail = Some(ptr);
+
+            if self.head.is_none() {
+                self.head = Some(ptr)
+            }
+        }
+    }
+
     pub fn pop_front(&mut self) -> Option<L::Handle> {
         unsafe {
             let head = self.head?;
@@ -118,6 +143,37 @@ where
             Some(L::from_raw(head))
         }
     }
+
+    /// unlinks `node` from the list, returning `None` if it isn't linked.
+    ///
+    /// # Safety
+    /// `node` must either be in this list, or not be in any list
+    pub unsafe fn remove(&mut self, node: NonNull<L::Target>) -> Option<L::Handle> {
+        unsafe {
+            let prev = L::pointers(node).as_ref().get_prev();
+            let next = L::pointers(node).as_ref().get_next();
+
+            match prev {
+                Some(prev) => L::pointers(prev).as_mut().set_next(next),
+                None => {
+                    if self.head != Some(node) {
+                        return None;
+                    }
+                    self.head = next;
+                }
+            }
+
+            match next {
+                Some(next) => L::pointers(next).as_mut().set_prev(prev),
+                None => self.tail = prev,
+            }
+
+            L::pointers(node).as_mut().set_prev(None);
+            L::pointers(node).as_mut().set_next(None);
+
+            Some(L::from_raw(node))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -168,4 +224,21 @@ mod tests {
         let v = list.pop_front();
         assert!(v.map(|v| v.val) == Some(e2.as_ref().val))
     }
+
+    #[test]
+    fn remove_from_middle() {
+        let mut list = LinkedList::<&Entry>::new();
+        let entries = [entry(0), entry(1), entry(2)];
+        list.push_back(entries[1].as_ref());
+        list.push_back(entries[0].as_ref());
+        list.push_front(entries[2].as_ref());
+
+        let removed = unsafe { list.remove(NonNull::from(entries[1].as_ref().get_ref())) };
+        assert_eq!(removed.map(|e| e.val), Some(1));
+        assert!(unsafe { list.remove(NonNull::from(entries[1].as_ref().get_ref())) }.is_none());
+
+        assert_eq!(list.pop_front().map(|e| e.val), Some(2));
+        assert_eq!(list.pop_front().map(|e| e.val), Some(0));
+        assert!(list.is_empty());
+    }
 }